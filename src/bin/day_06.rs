@@ -3,10 +3,15 @@ use std::str::FromStr;
 
 fn main() {
     let input = include_str!("../../inputs/day_06.txt").trim();
-    let mut model = Model::new(input).unwrap();
+    if std::env::args().any(|arg| arg == "--csv") {
+        let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
+        print!("{}", model.series(256).to_csv());
+        return;
+    }
+    let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
     model.run(80);
     println!("Part 1: {}", model.number_of_fish());
-    let mut model = Model::new(input).unwrap();
+    let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
     model.run(256);
     println!("Part 2: {}", model.number_of_fish());
 }
@@ -14,21 +19,46 @@ fn main() {
 #[derive(Debug)]
 struct Model {
     fish: Vec<Fish>,
+    lifecycle: Lifecycle,
+}
+
+#[derive(Debug)]
+struct Series {
+    initial: usize,
+    growth_rate: f64,
+    days: Vec<Day>,
+}
+
+#[derive(Debug)]
+struct Day {
+    day: usize,
+    total: usize,
+    histogram: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lifecycle {
+    reset: u8,
+    newborn: u8,
+    maturation: u8,
+    lifespan: Option<usize>,
 }
 
 #[derive(Debug)]
 struct Fish {
     timer: u8,
     count: usize,
+    maturing: u8,
+    cycles: usize,
 }
 
 impl Model {
-    fn new(input: &str) -> Result<Model, Error> {
+    fn new(input: &str, lifecycle: Lifecycle) -> Result<Model, Error> {
         let fish = input
             .split(',')
             .map(|n| n.parse())
             .collect::<Result<Vec<Fish>, _>>()?;
-        Ok(Model { fish })
+        Ok(Model { fish, lifecycle })
     }
 
     fn run(&mut self, times: usize) {
@@ -40,21 +70,137 @@ impl Model {
     fn run_one(&mut self) {
         let mut count = 0;
         for fish in &mut self.fish {
-            if fish.timer == 0 {
-                fish.timer = 6;
+            if fish.maturing > 0 {
+                fish.maturing -= 1;
+            } else if fish.timer == 0 {
+                fish.timer = self.lifecycle.reset;
+                fish.cycles += 1;
                 count += fish.count;
             } else {
                 fish.timer -= 1;
             }
         }
+        if let Some(lifespan) = self.lifecycle.lifespan {
+            self.fish.retain(|fish| fish.cycles < lifespan);
+        }
         if count > 0 {
-            self.fish.push(Fish { timer: 8, count });
+            self.fish.push(Fish {
+                timer: self.lifecycle.newborn,
+                count,
+                maturing: self.lifecycle.maturation,
+                cycles: 0,
+            });
+        }
+    }
+
+    fn series(&mut self, times: usize) -> Series {
+        let mut days = vec![self.day(0)];
+        for day in 1..=times {
+            self.run_one();
+            days.push(self.day(day));
+        }
+        Series {
+            initial: days[0].total,
+            growth_rate: self.lifecycle.growth_rate(),
+            days,
+        }
+    }
+
+    fn day(&self, day: usize) -> Day {
+        Day {
+            day,
+            total: self.number_of_fish(),
+            histogram: self.histogram(),
         }
     }
 
     fn number_of_fish(&self) -> usize {
         self.fish.iter().map(|fish| fish.count).sum()
     }
+
+    fn histogram(&self) -> Vec<usize> {
+        let max_timer = self
+            .fish
+            .iter()
+            .map(|fish| fish.timer)
+            .chain([self.lifecycle.reset, self.lifecycle.newborn])
+            .max()
+            .unwrap_or(0);
+        let mut histogram = vec![0; usize::from(max_timer) + 1];
+        for fish in &self.fish {
+            histogram[usize::from(fish.timer)] += fish.count;
+        }
+        histogram
+    }
+}
+
+impl Series {
+    fn estimate(&self, day: usize) -> f64 {
+        self.initial as f64 * self.growth_rate.powf(day as f64)
+    }
+
+    fn to_csv(&self) -> String {
+        let width = self
+            .days
+            .iter()
+            .map(|day| day.histogram.len())
+            .max()
+            .unwrap_or(0);
+        let mut csv = String::from("day,total,estimate");
+        for timer in 0..width {
+            csv.push_str(&format!(",timer_{}", timer));
+        }
+        csv.push('\n');
+        for day in &self.days {
+            csv.push_str(&format!(
+                "{},{},{:.3}",
+                day.day,
+                day.total,
+                self.estimate(day.day)
+            ));
+            for timer in 0..width {
+                csv.push_str(&format!(",{}", day.histogram.get(timer).unwrap_or(&0)));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl Lifecycle {
+    fn lanternfish() -> Lifecycle {
+        Lifecycle {
+            reset: 6,
+            newborn: 8,
+            maturation: 0,
+            lifespan: None,
+        }
+    }
+
+    fn growth_rate(&self) -> f64 {
+        // Solves the Euler-Lotka equation, 1 = sum(rate^-age) over the ages at
+        // which a single fish spawns, for the asymptotic daily growth rate.
+        let first = f64::from(self.newborn) + f64::from(self.maturation) + 1.;
+        let period = f64::from(self.reset) + 1.;
+        let offspring = |rate: f64| {
+            let ratio = rate.powf(-period);
+            let geometric = match self.lifespan {
+                Some(lifespan) => (1. - ratio.powf(lifespan as f64)) / (1. - ratio),
+                None => 1. / (1. - ratio),
+            };
+            rate.powf(-first) * geometric
+        };
+        let (mut low, mut high) = (1., 2.);
+        for _ in 0..100 {
+            let mid = (low + high) / 2.;
+            if offspring(mid) > 1. {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
 }
 
 impl FromStr for Fish {
@@ -63,6 +209,8 @@ impl FromStr for Fish {
         Ok(Fish {
             timer: s.parse()?,
             count: 1,
+            maturing: 0,
+            cycles: 0,
         })
     }
 }
@@ -70,13 +218,86 @@ impl FromStr for Fish {
 #[test]
 fn example() {
     let input = "3,4,3,1,2";
-    let mut model = Model::new(input).unwrap();
+    let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
     model.run(18);
     assert_eq!(model.number_of_fish(), 26);
-    let mut model = Model::new(input).unwrap();
+    let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
     model.run(80);
     assert_eq!(model.number_of_fish(), 5934);
-    let mut model = Model::new(input).unwrap();
+    let mut model = Model::new(input, Lifecycle::lanternfish()).unwrap();
     model.run(256);
     assert_eq!(model.number_of_fish(), 26984457539);
 }
+
+#[test]
+fn lifecycle() {
+    let input = "3,4,3,1,2";
+    let mut matured = Model::new(
+        input,
+        Lifecycle {
+            maturation: 2,
+            ..Lifecycle::lanternfish()
+        },
+    )
+    .unwrap();
+    let mut slow = Model::new(
+        input,
+        Lifecycle {
+            newborn: 10,
+            ..Lifecycle::lanternfish()
+        },
+    )
+    .unwrap();
+    matured.run(80);
+    slow.run(80);
+    assert_eq!(matured.number_of_fish(), slow.number_of_fish());
+
+    let mut model = Model::new(
+        "0",
+        Lifecycle {
+            lifespan: Some(1),
+            ..Lifecycle::lanternfish()
+        },
+    )
+    .unwrap();
+    model.run(100);
+    assert_eq!(model.number_of_fish(), 1);
+    let mut model = Model::new(
+        "0",
+        Lifecycle {
+            lifespan: Some(2),
+            ..Lifecycle::lanternfish()
+        },
+    )
+    .unwrap();
+    model.run(8);
+    assert_eq!(model.number_of_fish(), 2);
+    model.run(2);
+    assert_eq!(model.number_of_fish(), 3);
+}
+
+#[test]
+fn series() {
+    let mut model = Model::new("3,4,3,1,2", Lifecycle::lanternfish()).unwrap();
+    let series = model.series(18);
+    assert_eq!(series.days.len(), 19);
+    assert_eq!(series.days[0].histogram, vec![0, 1, 1, 2, 1, 0, 0, 0, 0]);
+    assert_eq!(series.days[18].total, 26);
+    assert_eq!(series.days[18].histogram.iter().sum::<usize>(), 26);
+    let csv = series.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "day,total,estimate,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8"
+    );
+    assert_eq!(lines.next().unwrap(), "0,5,5.000,0,1,1,2,1,0,0,0,0");
+    assert_eq!(lines.count(), 18);
+
+    let rate = Lifecycle::lanternfish().growth_rate();
+    assert!((rate.powi(9) - rate.powi(2) - 1.).abs() < 1e-9);
+    let mortal = Lifecycle {
+        lifespan: Some(1),
+        ..Lifecycle::lanternfish()
+    };
+    assert!((mortal.growth_rate() - 1.).abs() < 1e-9);
+}
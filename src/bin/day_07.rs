@@ -1,47 +1,370 @@
-use anyhow::Error;
-use std::i64;
+use anyhow::{anyhow, Error};
+use std::fmt;
 
 fn main() {
     let input = include_str!("../../inputs/day_07.txt").trim();
-    println!("Part 1: {}", least_fuel_to_align(input, false).unwrap());
-    println!("Part 2: {}", least_fuel_to_align(input, true).unwrap());
-}
-
-fn least_fuel_to_align(input: &str, advanced: bool) -> Result<i64, Error> {
-    let mut min = i64::MAX;
-    let mut max = i64::MIN;
-    let mut positions = Vec::new();
-    for result in input.split(',').map(|s| s.parse::<i64>()) {
-        let position = result?;
-        if position < min {
-            min = position;
-        } else if position > max {
-            max = position;
-        }
-        positions.push(position);
-    }
-    let mut min_fuel = i64::MAX;
-    for target in min..=max {
-        let fuel = if advanced {
-            positions.iter().fold(0, |acc, position| {
-                let difference = (position - target).abs();
-                acc + (1..=difference).sum::<i64>()
+    if std::env::args().any(|arg| arg == "--explain") {
+        let positions = positions(input).unwrap();
+        print!("{}", explain(&positions, &Triangular).unwrap());
+        return;
+    }
+    let alignment = least_fuel_to_align(input, fuel_cost("linear").unwrap().as_ref()).unwrap();
+    println!(
+        "Part 1: {} (position {})",
+        alignment.fuel, alignment.position
+    );
+    let alignment = least_fuel_to_align(input, fuel_cost("triangular").unwrap().as_ref()).unwrap();
+    println!(
+        "Part 2: {} (position {})",
+        alignment.fuel, alignment.position
+    );
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Alignment {
+    position: i64,
+    fuel: i64,
+}
+
+#[derive(Debug)]
+struct Report {
+    alignment: Alignment,
+    fuel_per_crab: Vec<(i64, i64)>,
+    curve: Vec<(i64, i64)>,
+}
+
+trait FuelCost {
+    fn fuel(&self, distance: i64) -> i64;
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    fn candidates(&self, _positions: &[i64]) -> Option<Vec<i64>> {
+        None
+    }
+}
+
+struct Linear;
+
+struct Triangular;
+
+struct Quadratic;
+
+struct Capped {
+    cost: Box<dyn FuelCost>,
+    cap: i64,
+}
+
+/// A user-supplied cost function. Only set `convex` when the total fuel is
+/// convex in the target position, otherwise every position is checked.
+struct Custom<F> {
+    fuel: F,
+    convex: bool,
+}
+
+impl FuelCost for Linear {
+    fn fuel(&self, distance: i64) -> i64 {
+        distance
+    }
+
+    fn candidates(&self, positions: &[i64]) -> Option<Vec<i64>> {
+        let mut sorted = positions.to_vec();
+        sorted.sort_unstable();
+        Some(vec![sorted[sorted.len() / 2]])
+    }
+}
+
+impl FuelCost for Triangular {
+    fn fuel(&self, distance: i64) -> i64 {
+        distance * (distance + 1) / 2
+    }
+
+    fn candidates(&self, positions: &[i64]) -> Option<Vec<i64>> {
+        // The continuous optimum of the triangular cost lies within 1/2 of the
+        // mean, so the integer optimum is one of the positions around it.
+        let mean = positions
+            .iter()
+            .sum::<i64>()
+            .div_euclid(positions.len() as i64);
+        Some(((mean - 1)..=(mean + 2)).collect())
+    }
+}
+
+impl FuelCost for Quadratic {
+    fn fuel(&self, distance: i64) -> i64 {
+        distance * distance
+    }
+}
+
+impl FuelCost for Capped {
+    fn fuel(&self, distance: i64) -> i64 {
+        self.cost.fuel(distance).min(self.cap)
+    }
+
+    fn is_convex(&self) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(i64) -> i64> FuelCost for Custom<F> {
+    fn fuel(&self, distance: i64) -> i64 {
+        (self.fuel)(distance)
+    }
+
+    fn is_convex(&self) -> bool {
+        self.convex
+    }
+}
+
+fn fuel_cost(name: &str) -> Result<Box<dyn FuelCost>, Error> {
+    if let Some(cap) = name.strip_prefix("capped:") {
+        let (cap, cost) = cap.split_once(':').unwrap_or((cap, "linear"));
+        return Ok(Box::new(Capped {
+            cost: fuel_cost(cost)?,
+            cap: cap.parse()?,
+        }));
+    }
+    if let Some(exponent) = name.strip_prefix("power:") {
+        let exponent: f64 = exponent.parse()?;
+        if !exponent.is_finite() || exponent < 0. {
+            return Err(anyhow!("Invalid exponent: {}", exponent));
+        }
+        return Ok(Box::new(Custom {
+            fuel: move |distance: i64| (distance as f64).powf(exponent) as i64,
+            convex: exponent >= 1. && exponent.fract() == 0.,
+        }));
+    }
+    match name {
+        "linear" => Ok(Box::new(Linear)),
+        "triangular" => Ok(Box::new(Triangular)),
+        "quadratic" => Ok(Box::new(Quadratic)),
+        _ => Err(anyhow!("Unknown fuel cost: {}", name)),
+    }
+}
+
+fn least_fuel_to_align(input: &str, cost: &dyn FuelCost) -> Result<Alignment, Error> {
+    let positions = positions(input)?;
+    optimal_alignment(&positions, cost)
+}
+
+fn positions(input: &str) -> Result<Vec<i64>, Error> {
+    input
+        .split(',')
+        .map(|s| s.parse().map_err(Error::from))
+        .collect()
+}
+
+fn optimal_alignment(positions: &[i64], cost: &dyn FuelCost) -> Result<Alignment, Error> {
+    let min = *positions
+        .iter()
+        .min()
+        .ok_or_else(|| anyhow!("No crab positions"))?;
+    let max = *positions.iter().max().unwrap();
+    let candidates = if let Some(candidates) = cost.candidates(positions) {
+        candidates
+    } else if cost.is_convex() {
+        ternary_search(min, max, |target| fuel(positions, target, cost))?
+    } else {
+        (min..=max).collect()
+    };
+    candidates
+        .into_iter()
+        .map(|position| {
+            Ok(Alignment {
+                position,
+                fuel: fuel(positions, position, cost)?,
             })
+        })
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
+        .min_by_key(|alignment| alignment.fuel)
+        .ok_or_else(|| anyhow!("No candidate positions"))
+}
+
+fn explain(positions: &[i64], cost: &dyn FuelCost) -> Result<Report, Error> {
+    let alignment = optimal_alignment(positions, cost)?;
+    let fuel_per_crab = positions
+        .iter()
+        .map(|&position| (position, cost.fuel((position - alignment.position).abs())))
+        .collect();
+    let min = *positions.iter().min().unwrap();
+    let max = *positions.iter().max().unwrap();
+    let curve = (min..=max)
+        .map(|target| Ok((target, fuel(positions, target, cost)?)))
+        .collect::<Result<_, Error>>()?;
+    Ok(Report {
+        alignment,
+        fuel_per_crab,
+        curve,
+    })
+}
+
+fn ternary_search(
+    mut low: i64,
+    mut high: i64,
+    f: impl Fn(i64) -> Result<i64, Error>,
+) -> Result<Vec<i64>, Error> {
+    while high - low > 2 {
+        let a = low + (high - low) / 3;
+        let b = high - (high - low) / 3;
+        let (fuel_a, fuel_b) = (f(a)?, f(b)?);
+        if fuel_a < fuel_b {
+            high = b - 1;
+        } else if fuel_a > fuel_b {
+            low = a + 1;
         } else {
-            positions
-                .iter()
-                .fold(0, |acc, position| (position - target).abs() + acc)
-        };
-        if fuel < min_fuel {
-            min_fuel = fuel;
+            low = a;
+            high = b;
         }
     }
-    Ok(min_fuel)
+    Ok((low..=high).collect())
+}
+
+fn fuel(positions: &[i64], target: i64, cost: &dyn FuelCost) -> Result<i64, Error> {
+    positions.iter().try_fold(0i64, |total, position| {
+        total
+            .checked_add(cost.fuel((position - target).abs()))
+            .ok_or_else(|| anyhow!("Fuel overflowed aligning to {}", target))
+    })
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Target: {}", self.alignment.position)?;
+        writeln!(f, "Fuel: {}", self.alignment.fuel)?;
+        writeln!(f, "Fuel per crab:")?;
+        for (position, fuel) in &self.fuel_per_crab {
+            writeln!(f, "  {}: {}", position, fuel)?;
+        }
+        writeln!(f, "Cost curve:")?;
+        for (target, fuel) in &self.curve {
+            writeln!(f, "  {}: {}", target, fuel)?;
+        }
+        Ok(())
+    }
 }
 
 #[test]
 fn example() {
     let input = "16,1,2,0,4,2,7,1,2,14";
-    assert_eq!(least_fuel_to_align(input, false).unwrap(), 37);
-    assert_eq!(least_fuel_to_align(input, true).unwrap(), 168);
+    assert_eq!(least_fuel_to_align(input, &Linear).unwrap().fuel, 37);
+    assert_eq!(least_fuel_to_align(input, &Triangular).unwrap().fuel, 168);
+    let positions = positions(input).unwrap();
+    assert_eq!(
+        optimal_alignment(&positions, &Linear).unwrap(),
+        Alignment {
+            position: 2,
+            fuel: 37
+        }
+    );
+    assert_eq!(
+        optimal_alignment(&positions, &Triangular).unwrap(),
+        Alignment {
+            position: 5,
+            fuel: 168
+        }
+    );
+}
+
+#[test]
+fn matches_brute_force() {
+    let inputs = [
+        "1",
+        "0,100",
+        "3,3,3,9",
+        "1,2,3,4,5,6,7,8,50",
+        "7,0,0,0,1000,2,2",
+        "0,0,1,60,61,100,100",
+    ];
+    let costs: Vec<Box<dyn FuelCost>> = vec![
+        Box::new(Linear),
+        Box::new(Triangular),
+        Box::new(Quadratic),
+        fuel_cost("capped:10").unwrap(),
+        fuel_cost("capped:100:quadratic").unwrap(),
+        fuel_cost("power:0.5").unwrap(),
+        fuel_cost("power:3").unwrap(),
+        Box::new(Custom {
+            fuel: |distance: i64| distance.pow(3),
+            convex: true,
+        }),
+        Box::new(Custom {
+            fuel: |distance: i64| ((distance as f64).sqrt() * 10.) as i64,
+            convex: false,
+        }),
+    ];
+    for input in inputs {
+        let positions = positions(input).unwrap();
+        let min = *positions.iter().min().unwrap();
+        let max = *positions.iter().max().unwrap();
+        for cost in &costs {
+            let brute_force = (min..=max)
+                .map(|target| fuel(&positions, target, cost.as_ref()).unwrap())
+                .min()
+                .unwrap();
+            assert_eq!(
+                optimal_alignment(&positions, cost.as_ref()).unwrap().fuel,
+                brute_force
+            );
+        }
+    }
+    let positions = positions("0,0,1,60,61,100,100").unwrap();
+    let cost = Custom {
+        fuel: |distance: i64| ((distance as f64).sqrt() * 10.) as i64,
+        convex: false,
+    };
+    assert_eq!(
+        optimal_alignment(&positions, &cost).unwrap(),
+        Alignment {
+            position: 0,
+            fuel: 365
+        }
+    );
+}
+
+#[test]
+fn invalid_costs() {
+    assert!(fuel_cost("power:-1").is_err());
+    assert!(fuel_cost("power:inf").is_err());
+    assert!(fuel_cost("power:NaN").is_err());
+    assert!(fuel_cost("power:x").is_err());
+    let cost = fuel_cost("power:40").unwrap();
+    assert!(optimal_alignment(&[0, 0, 5], cost.as_ref()).is_err());
+    assert!(explain(&[0, 0, 5], cost.as_ref()).is_err());
+    let cost = fuel_cost("power:0").unwrap();
+    assert_eq!(
+        optimal_alignment(&[0, 0, 5], cost.as_ref()).unwrap().fuel,
+        3
+    );
+}
+
+#[test]
+fn report() {
+    let positions = positions("16,1,2,0,4,2,7,1,2,14").unwrap();
+    let report = explain(&positions, &Triangular).unwrap();
+    assert_eq!(
+        report.alignment,
+        Alignment {
+            position: 5,
+            fuel: 168
+        }
+    );
+    assert_eq!(report.fuel_per_crab[0], (16, 66));
+    assert_eq!(report.fuel_per_crab[1], (1, 10));
+    assert_eq!(
+        report
+            .fuel_per_crab
+            .iter()
+            .map(|(_, fuel)| fuel)
+            .sum::<i64>(),
+        168
+    );
+    assert_eq!(report.curve.len(), 17);
+    assert_eq!(report.curve[2], (2, 206));
+    assert_eq!(
+        report.curve.iter().min_by_key(|(_, fuel)| *fuel).unwrap(),
+        &(5, 168)
+    );
+    assert!(report.to_string().starts_with("Target: 5\nFuel: 168\n"));
 }
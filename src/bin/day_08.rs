@@ -1,13 +1,18 @@
 use anyhow::{anyhow, Error};
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+};
+use thiserror::Error;
 
 fn main() {
     let input = include_str!("../../inputs/day_08.txt");
-    println!("Part 1: {}", number_of_easy_digits(input).unwrap());
-    println!("Part 2: {}", sum_of_output_values(input).unwrap());
+    let layout = Layout::seven_segment();
+    println!("Part 1: {}", number_of_easy_digits(input, &layout).unwrap());
+    println!("Part 2: {}", sum_of_output_values(input, &layout).unwrap());
 }
 
-fn number_of_easy_digits(input: &str) -> Result<usize, Error> {
+fn number_of_easy_digits(input: &str, layout: &Layout) -> Result<usize, Error> {
     let mut count = 0;
     for line in input.lines() {
         let parts: Vec<_> = line.split(" | ").collect();
@@ -16,100 +21,299 @@ fn number_of_easy_digits(input: &str) -> Result<usize, Error> {
         }
         count += parts[1]
             .split_whitespace()
-            .filter(|letters| letters.len() <= 4 || letters.len() == 7)
+            .filter(|letters| {
+                layout
+                    .glyphs
+                    .iter()
+                    .filter(|glyph| glyph.segments.len() == letters.len())
+                    .count()
+                    == 1
+            })
             .count();
     }
     Ok(count)
 }
 
-fn sum_of_output_values(input: &str) -> Result<u64, Error> {
+fn sum_of_output_values(input: &str, layout: &Layout) -> Result<u64, Error> {
     let mut sum = 0;
     for line in input.lines() {
-        sum += output_value(line)?;
+        sum += output_value(line, layout)?;
     }
     Ok(sum)
 }
 
-fn output_value(line: &str) -> Result<u64, Error> {
+fn output_value(line: &str, layout: &Layout) -> Result<u64, Error> {
+    let reading = read(line, layout)?;
+    if !reading.unmapped.is_empty() {
+        return Err(anyhow!(
+            "Could not map output patterns: {}",
+            reading.unmapped.join(" ")
+        ));
+    }
+    reading
+        .value
+        .ok_or_else(|| anyhow!("Output is not a number: {}", reading.digits))
+}
+
+fn read(line: &str, layout: &Layout) -> Result<Reading, Error> {
     let parts: Vec<_> = line.split(" | ").collect();
     if parts.len() != 2 {
         return Err(anyhow!("Invalid line: {}", line));
     }
     let examples: Vec<_> = parts[0].split_whitespace().collect();
-    if examples.len() != 10 {
-        return Err(anyhow!("Invalid examples: {}", parts[0]));
-    }
-    let map = Map::new(&examples)?;
-    let digits: Vec<_> = parts[1].split_whitespace().collect();
-    if digits.len() != 4 {
-        return Err(anyhow!("Invalid number of digits: {}", parts[1]));
+    let map = Map::new(&examples, layout)?;
+    let mut digits = String::new();
+    let mut unmapped = Vec::new();
+    for pattern in parts[1].split_whitespace() {
+        if let Some(symbol) = map.decode(pattern) {
+            digits.push(symbol);
+        } else {
+            digits.push('?');
+            unmapped.push(pattern.to_string());
+        }
     }
-    Ok(map.decode(digits[0]) * 1000
-        + map.decode(digits[1]) * 100
-        + map.decode(digits[2]) * 10
-        + map.decode(digits[3]))
+    let value = match layout.radix() {
+        Some(radix) if unmapped.is_empty() => u64::from_str_radix(&digits, radix).ok(),
+        _ => None,
+    };
+    Ok(Reading {
+        digits,
+        value,
+        unmapped,
+    })
+}
+
+#[derive(Debug)]
+struct Reading {
+    digits: String,
+    value: Option<u64>,
+    unmapped: Vec<String>,
 }
 
 #[derive(Debug)]
-struct Map(HashMap<BTreeSet<char>, u64>);
+struct Layout {
+    segments: BTreeSet<char>,
+    glyphs: Vec<Glyph>,
+}
+
+#[derive(Debug)]
+struct Glyph {
+    symbol: char,
+    segments: BTreeSet<char>,
+}
+
+#[derive(Debug)]
+struct Map(HashMap<BTreeSet<char>, char>);
+
+#[derive(Debug)]
+struct Solver {
+    patterns: Vec<BTreeSet<char>>,
+    glyphs: Vec<BTreeSet<char>>,
+    solutions: Vec<Vec<BTreeSet<char>>>,
+}
+
+#[derive(Error, Debug)]
+enum SolveError {
+    #[error("no wiring is consistent with the patterns: {0}")]
+    Unsatisfiable(String),
+    #[error("more than one wiring is consistent with the patterns: {0}")]
+    Ambiguous(String),
+    #[error("unknown segment: {0}")]
+    UnknownSegment(char),
+}
 
 impl Map {
-    fn new(examples: &[&str]) -> Result<Map, Error> {
-        let mut map = HashMap::new();
-        let mut examples: Vec<BTreeSet<char>> = examples
+    fn new(examples: &[&str], layout: &Layout) -> Result<Map, SolveError> {
+        let glyphs: Vec<BTreeSet<char>> = layout
+            .glyphs
             .iter()
-            .map(|example| example.chars().collect())
+            .map(|glyph| glyph.segments.clone())
             .collect();
-        examples.sort_by_key(|example| example.len());
-        let one = examples.remove(0);
-        let seven = examples.remove(0);
-        let four = examples.remove(0);
-        let eight = examples.pop().unwrap();
-        let (mut five_len, mut six_len): (Vec<_>, Vec<_>) =
-            examples.into_iter().partition(|example| example.len() == 5);
-
-        let nine_index = six_len
-            .iter()
-            .position(|example| example.is_superset(&four))
-            .unwrap();
-        let nine = six_len.remove(nine_index);
-        let zero_index = six_len
+        let segments = &layout.segments;
+        let mut patterns: Vec<BTreeSet<char>> = Vec::new();
+        for example in examples {
+            let pattern: BTreeSet<char> = example.chars().collect();
+            if let Some(&c) = pattern.iter().find(|c| !segments.contains(c)) {
+                return Err(SolveError::UnknownSegment(c));
+            }
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+        patterns.sort_by_key(|pattern| {
+            glyphs
+                .iter()
+                .filter(|glyph| glyph.len() == pattern.len())
+                .count()
+        });
+        let mut solver = Solver {
+            patterns,
+            glyphs,
+            solutions: Vec::new(),
+        };
+        let possible = segments
             .iter()
-            .position(|example| example.is_superset(&one))
-            .unwrap();
-        let zero = six_len.remove(zero_index);
-        let six = six_len.remove(0);
-        assert!(six_len.is_empty());
+            .map(|&wire| (wire, segments.clone()))
+            .collect();
+        solver.assign(0, &mut vec![false; layout.glyphs.len()], &possible);
+        match solver.solutions.len() {
+            0 => Err(SolveError::Unsatisfiable(examples.join(" "))),
+            1 => Ok(Map(solver
+                .solutions
+                .pop()
+                .unwrap()
+                .into_iter()
+                .zip(layout.glyphs.iter().map(|glyph| glyph.symbol))
+                .collect())),
+            _ => Err(SolveError::Ambiguous(examples.join(" "))),
+        }
+    }
 
-        let three_index = five_len
-            .iter()
-            .position(|example| example.is_superset(&seven))
+    fn decode(&self, digit: &str) -> Option<char> {
+        let set: BTreeSet<char> = digit.chars().collect();
+        self.0.get(&set).cloned()
+    }
+}
+
+impl Layout {
+    fn seven_segment() -> Layout {
+        "0:abcefg 1:cf 2:acdeg 3:acdfg 4:bcdf 5:abdfg 6:abdefg 7:acf 8:abcdefg 9:abcdfg"
+            .parse()
+            .unwrap()
+    }
+
+    fn hexadecimal() -> Layout {
+        let mut layout = Layout::seven_segment();
+        let letters: Layout = "A:abcdef b:bdefg C:abeg d:cdefg E:abdeg F:abde"
+            .parse()
             .unwrap();
-        let three = five_len.remove(three_index);
-        let five_index = five_len
+        layout.glyphs.extend(letters.glyphs);
+        layout
+    }
+
+    fn radix(&self) -> Option<u32> {
+        let mut values = self
+            .glyphs
             .iter()
-            .position(|example| nine.is_superset(example))
-            .unwrap();
-        let five = five_len.remove(five_index);
-        let two = five_len.remove(0);
-        assert!(five_len.is_empty());
-
-        map.insert(zero, 0);
-        map.insert(one, 1);
-        map.insert(two, 2);
-        map.insert(three, 3);
-        map.insert(four, 4);
-        map.insert(five, 5);
-        map.insert(six, 6);
-        map.insert(seven, 7);
-        map.insert(eight, 8);
-        map.insert(nine, 9);
-        Ok(Map(map))
+            .map(|glyph| glyph.symbol.to_digit(36))
+            .collect::<Option<Vec<u32>>>()?;
+        values.sort_unstable();
+        if values.iter().copied().eq(0..values.len() as u32) && values.len() > 1 {
+            Some(values.len() as u32)
+        } else {
+            None
+        }
     }
+}
 
-    fn decode(&self, digit: &str) -> u64 {
-        let set: BTreeSet<char> = digit.chars().collect();
-        self.0[&set]
+impl FromStr for Layout {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Layout, Error> {
+        match s {
+            "seven-segment" => return Ok(Layout::seven_segment()),
+            "hexadecimal" => return Ok(Layout::hexadecimal()),
+            _ => {}
+        }
+        let mut glyphs: Vec<Glyph> = Vec::new();
+        for definition in s.split_whitespace() {
+            let (symbol, segments) = definition
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid glyph definition: {}", definition))?;
+            let mut chars = symbol.chars();
+            let symbol = match (chars.next(), chars.next()) {
+                (Some(symbol), None) => symbol,
+                _ => return Err(anyhow!("Invalid glyph symbol: {}", symbol)),
+            };
+            let glyph = Glyph {
+                symbol,
+                segments: segments.chars().collect(),
+            };
+            if glyphs.iter().any(|other| other.symbol == glyph.symbol) {
+                return Err(anyhow!("Duplicate glyph symbol: {}", symbol));
+            }
+            if glyphs.iter().any(|other| other.segments == glyph.segments) {
+                return Err(anyhow!("Duplicate glyph segments: {}", segments));
+            }
+            glyphs.push(glyph);
+        }
+        if glyphs.is_empty() {
+            return Err(anyhow!("Empty layout"));
+        }
+        Ok(Layout {
+            segments: glyphs
+                .iter()
+                .flat_map(|glyph| glyph.segments.iter().cloned())
+                .collect(),
+            glyphs,
+        })
+    }
+}
+
+impl Solver {
+    fn assign(
+        &mut self,
+        index: usize,
+        used: &mut [bool],
+        possible: &BTreeMap<char, BTreeSet<char>>,
+    ) {
+        if self.solutions.len() > 1 {
+            return;
+        }
+        let pattern = if let Some(pattern) = self.patterns.get(index) {
+            pattern.clone()
+        } else {
+            self.connect(possible, BTreeMap::new());
+            return;
+        };
+        for i in 0..self.glyphs.len() {
+            let glyph = &self.glyphs[i];
+            if used[i] || glyph.len() != pattern.len() {
+                continue;
+            }
+            let mut possible = possible.clone();
+            for (wire, segments) in possible.iter_mut() {
+                let lit = pattern.contains(wire);
+                segments.retain(|segment| glyph.contains(segment) == lit);
+            }
+            if possible.values().any(|segments| segments.is_empty()) {
+                continue;
+            }
+            used[i] = true;
+            self.assign(index + 1, used, &possible);
+            used[i] = false;
+        }
+    }
+
+    fn connect(&mut self, possible: &BTreeMap<char, BTreeSet<char>>, wiring: BTreeMap<char, char>) {
+        if self.solutions.len() > 1 {
+            return;
+        }
+        let wire = if let Some(&wire) = possible.keys().find(|wire| !wiring.contains_key(wire)) {
+            wire
+        } else {
+            let solution = self
+                .glyphs
+                .iter()
+                .map(|glyph| {
+                    wiring
+                        .iter()
+                        .filter(|(_, segment)| glyph.contains(segment))
+                        .map(|(&wire, _)| wire)
+                        .collect()
+                })
+                .collect();
+            if !self.solutions.contains(&solution) {
+                self.solutions.push(solution);
+            }
+            return;
+        };
+        for &segment in &possible[&wire] {
+            if wiring.values().all(|&other| other != segment) {
+                let mut wiring = wiring.clone();
+                wiring.insert(wire, segment);
+                self.connect(possible, wiring);
+            }
+        }
     }
 }
 
@@ -127,10 +331,84 @@ bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbg
 egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
 gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
 ";
-    assert_eq!(number_of_easy_digits(input).unwrap(), 26);
+    let layout = Layout::seven_segment();
+    assert_eq!(number_of_easy_digits(input, &layout).unwrap(), 26);
 
     let one_line =
         "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
-    assert_eq!(output_value(one_line).unwrap(), 5353);
-    assert_eq!(sum_of_output_values(input).unwrap(), 61229);
+    assert_eq!(output_value(one_line, &layout).unwrap(), 5353);
+    assert_eq!(sum_of_output_values(input, &layout).unwrap(), 61229);
+}
+
+#[test]
+fn solver_errors() {
+    let layout = Layout::seven_segment();
+    assert!(matches!(
+        Map::new(&["ab", "abc"], &layout),
+        Err(SolveError::Ambiguous(_))
+    ));
+    assert!(matches!(
+        Map::new(&["ab", "cd"], &layout),
+        Err(SolveError::Unsatisfiable(_))
+    ));
+    assert!(matches!(
+        Map::new(&["ab", "xyz"], &layout),
+        Err(SolveError::UnknownSegment('x'))
+    ));
+    let layout: Layout = "x:ab y:abc z:cd".parse().unwrap();
+    let map = Map::new(&["ab", "abc"], &layout).unwrap();
+    assert_eq!(map.decode("ba"), Some('x'));
+    assert_eq!(map.decode("dc"), Some('z'));
+}
+
+#[test]
+fn layouts() {
+    let layout = Layout::hexadecimal();
+    let scramble: HashMap<char, char> = "abcdefg".chars().zip("gfaebdc".chars()).collect();
+    let examples: Vec<String> = layout
+        .glyphs
+        .iter()
+        .map(|glyph| glyph.segments.iter().map(|c| scramble[c]).collect())
+        .collect();
+    let examples: Vec<&str> = examples.iter().map(|s| s.as_str()).collect();
+    let map = Map::new(&examples, &layout).unwrap();
+    let decoded: String = examples
+        .iter()
+        .map(|example| map.decode(example).unwrap())
+        .collect();
+    assert_eq!(decoded, "0123456789AbCdEF");
+
+    assert!("0:ab 1:ab".parse::<Layout>().is_err());
+    assert!("0:ab 0:abc".parse::<Layout>().is_err());
+    assert!("01:ab".parse::<Layout>().is_err());
+}
+
+#[test]
+fn readings() {
+    let layout = Layout::seven_segment();
+    let reading = read(
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb ab fcadb",
+        &layout,
+    )
+    .unwrap();
+    assert_eq!(reading.digits, "513");
+    assert_eq!(reading.value, Some(513));
+    assert!(reading.unmapped.is_empty());
+
+    let reading = read(
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | ab ac dab",
+        &layout,
+    )
+    .unwrap();
+    assert_eq!(reading.digits, "1?7");
+    assert_eq!(reading.value, None);
+    assert_eq!(reading.unmapped, vec!["ac"]);
+    assert!(output_value(
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | ab ac dab",
+        &layout
+    )
+    .is_err());
+
+    assert_eq!(Layout::hexadecimal().radix(), Some(16));
+    assert_eq!("x:ab y:abc".parse::<Layout>().unwrap().radix(), None);
 }
@@ -1,9 +1,50 @@
 use anyhow::{anyhow, Error};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 fn main() {
     let input = include_str!("../../inputs/day_09.txt");
     let map = Map::new(input).unwrap();
+    if std::env::args().any(|arg| arg == "--labels") {
+        print!("{}", map.labels());
+        for basin in map.basins() {
+            println!(
+                "Basin {}: low point ({}, {}), size {}",
+                basin.id,
+                basin.low_point.x,
+                basin.low_point.y,
+                basin.size()
+            );
+        }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--drainage") {
+        let drainage = map.drainage();
+        let mut sinks: Vec<&Point> = drainage.sinks.iter().collect();
+        sinks.sort_unstable_by_key(|point| (point.y, point.x));
+        for sink in sinks {
+            let kind = if drainage.flats.contains(sink) {
+                "Flat"
+            } else {
+                "Low point"
+            };
+            println!(
+                "{} ({}, {}): {} cells",
+                kind, sink.x, sink.y, drainage.accumulation[sink]
+            );
+        }
+        for tie in &drainage.ties {
+            let next = drainage.downstream[tie];
+            let sink = drainage.drains_to[tie];
+            println!(
+                "Tie at ({}, {}) flows to ({}, {}) and drains to ({}, {})",
+                tie.x, tie.y, next.x, next.y, sink.x, sink.y
+            );
+        }
+        return;
+    }
     println!("Part 1: {}", map.risk_level());
     println!(
         "Part 2: {}",
@@ -14,7 +55,31 @@ fn main() {
 #[derive(Debug)]
 struct Map(HashMap<Point, u32>);
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug)]
+struct Basin {
+    id: usize,
+    low_point: Point,
+    cells: Vec<Point>,
+}
+
+#[derive(Debug)]
+struct Labels {
+    width: i32,
+    height: i32,
+    labels: HashMap<Point, usize>,
+}
+
+#[derive(Debug)]
+struct Drainage {
+    downstream: HashMap<Point, Point>,
+    drains_to: HashMap<Point, Point>,
+    accumulation: HashMap<Point, usize>,
+    sinks: HashSet<Point>,
+    flats: HashSet<Point>,
+    ties: Vec<Point>,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 struct Point {
     x: i32,
     y: i32,
@@ -60,43 +125,146 @@ impl Map {
     }
 
     fn three_largest_basin_sizes_multiplied(&self) -> Result<usize, Error> {
-        let mut basins = self.basins();
-        if basins.len() < 3 {
-            return Err(anyhow!("Too few basins: {}", basins.len()));
+        let mut sizes: Vec<usize> = self.basins().iter().map(|basin| basin.size()).collect();
+        if sizes.len() < 3 {
+            return Err(anyhow!("Too few basins: {}", sizes.len()));
         }
-        basins.sort_unstable();
-        Ok(basins.iter().rev().take(3).product())
+        sizes.sort_unstable();
+        Ok(sizes.iter().rev().take(3).product())
     }
 
-    fn basins(&self) -> Vec<usize> {
+    fn basins(&self) -> Vec<Basin> {
+        let mut points: Vec<Point> = self.0.keys().cloned().collect();
+        points.sort_unstable_by_key(|point| (point.y, point.x));
         let mut basins = Vec::new();
         let mut seen = HashSet::new();
-        for (&point, &height) in self.0.iter() {
-            if !seen.contains(&point) {
-                seen.insert(point);
-                if height != 9 {
-                    let mut basin = HashSet::new();
-                    basin.insert(point);
-                    self.grow(point, &mut basin, &mut seen);
-                    basins.push(basin);
+        for point in points {
+            if self.0[&point] == 9 || !seen.insert(point) {
+                continue;
+            }
+            let mut cells = Vec::new();
+            let mut stack = vec![point];
+            while let Some(point) = stack.pop() {
+                cells.push(point);
+                for point in point.adjacent() {
+                    if let Some(&height) = self.0.get(&point) {
+                        if height != 9 && seen.insert(point) {
+                            stack.push(point);
+                        }
+                    }
                 }
             }
+            cells.sort_unstable_by_key(|point| (point.y, point.x));
+            let low_point = *cells
+                .iter()
+                .min_by_key(|point| self.0[point])
+                .expect("basins have at least one cell");
+            basins.push(Basin {
+                id: basins.len(),
+                low_point,
+                cells,
+            });
         }
-        basins.into_iter().map(|basin| basin.len()).collect()
+        basins
     }
 
-    fn grow(&self, point: Point, basin: &mut HashSet<Point>, seen: &mut HashSet<Point>) {
-        for point in point.adjacent() {
-            if !seen.contains(&point) {
-                if let Some(&height) = self.0.get(&point) {
-                    seen.insert(point);
-                    if height != 9 {
-                        basin.insert(point);
-                        self.grow(point, basin, seen);
+    fn labels(&self) -> Labels {
+        let mut labels = HashMap::new();
+        for basin in self.basins() {
+            for point in basin.cells {
+                labels.insert(point, basin.id);
+            }
+        }
+        Labels {
+            width: self.0.keys().map(|point| point.x + 1).max().unwrap_or(0),
+            height: self.0.keys().map(|point| point.y + 1).max().unwrap_or(0),
+            labels,
+        }
+    }
+
+    fn drainage(&self) -> Drainage {
+        let mut points: Vec<Point> = self.0.keys().cloned().collect();
+        points.sort_unstable_by_key(|point| (self.0[point], point.y, point.x));
+        let mut downstream = HashMap::new();
+        let mut sinks = HashSet::new();
+        let mut flats = HashSet::new();
+        let mut ties = Vec::new();
+        for &point in &points {
+            let height = self.0[&point];
+            let neighbors: Vec<(Point, u32)> = point
+                .adjacent()
+                .iter()
+                .filter_map(|point| self.0.get(point).map(|&height| (*point, height)))
+                .collect();
+            match neighbors.iter().map(|&(_, height)| height).min() {
+                Some(lowest) if lowest < height => {
+                    let mut lowest = neighbors.iter().filter(|&&(_, other)| other == lowest);
+                    downstream.insert(point, lowest.next().unwrap().0);
+                    if lowest.next().is_some() {
+                        ties.push(point);
+                    }
+                }
+                _ => {
+                    sinks.insert(point);
+                    if !self.is_low_point(height, &point) {
+                        flats.insert(point);
                     }
                 }
             }
         }
+        let mut drains_to = HashMap::new();
+        for &point in &points {
+            let sink = downstream
+                .get(&point)
+                .map(|next| drains_to[next])
+                .unwrap_or(point);
+            drains_to.insert(point, sink);
+        }
+        let mut accumulation: HashMap<Point, usize> = HashMap::new();
+        for point in points.iter().rev() {
+            let count = *accumulation.entry(*point).or_insert(0) + 1;
+            accumulation.insert(*point, count);
+            if let Some(next) = downstream.get(point) {
+                *accumulation.entry(*next).or_insert(0) += count;
+            }
+        }
+        ties.sort_unstable_by_key(|point| (point.y, point.x));
+        Drainage {
+            downstream,
+            drains_to,
+            accumulation,
+            sinks,
+            flats,
+            ties,
+        }
+    }
+}
+
+impl Basin {
+    fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+/// Every cell is as wide as the largest basin id, so ids never collide.
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .labels
+            .values()
+            .max()
+            .map(|id| id.to_string().len())
+            .unwrap_or(1);
+        for y in 0..self.height {
+            let cells: Vec<String> = (0..self.width)
+                .map(|x| match self.labels.get(&Point { x, y }) {
+                    Some(id) => format!("{:>width$}", id, width = width),
+                    None => format!("{:>width$}", "#", width = width),
+                })
+                .collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        Ok(())
     }
 }
 
@@ -134,3 +302,90 @@ fn example() {
     assert_eq!(map.risk_level(), 15);
     assert_eq!(map.three_largest_basin_sizes_multiplied().unwrap(), 1134);
 }
+
+#[test]
+fn basins() {
+    let input = "2199943210
+3987894921
+9856789892
+8767896789
+9899965678";
+    let map = Map::new(input).unwrap();
+    let basins = map.basins();
+    assert_eq!(basins.len(), 4);
+    assert_eq!(basins[0].id, 0);
+    assert_eq!(basins[0].low_point, Point { x: 1, y: 0 });
+    assert_eq!(basins[0].size(), 3);
+    assert_eq!(
+        basins[0].cells,
+        vec![
+            Point { x: 0, y: 0 },
+            Point { x: 1, y: 0 },
+            Point { x: 0, y: 1 }
+        ]
+    );
+    assert_eq!(basins[1].low_point, Point { x: 9, y: 0 });
+    assert_eq!(basins[1].size(), 9);
+    assert_eq!(
+        map.labels().to_string(),
+        "0 0 # # # 1 1 1 1 1
+0 # 2 2 2 # 1 # 1 1
+# 2 2 2 2 2 # 3 # 1
+2 2 2 2 2 # 3 3 3 #
+# 2 # # # 3 3 3 3 3
+"
+    );
+    let map = Map::new("0909090909090909090909").unwrap();
+    assert_eq!(
+        map.labels().to_string(),
+        " 0  #  1  #  2  #  3  #  4  #  5  #  6  #  7  #  8  #  9  # 10  #\n"
+    );
+
+    let input = "0"
+        .repeat(300)
+        .lines()
+        .cycle()
+        .take(300)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let map = Map::new(&input).unwrap();
+    assert_eq!(map.basins()[0].size(), 90000);
+}
+
+#[test]
+fn drainage() {
+    let input = "2199943210
+3987894921
+9856789892
+8767896789
+9899965678";
+    let map = Map::new(input).unwrap();
+    let drainage = map.drainage();
+    assert_eq!(drainage.sinks.len(), 4);
+    assert!(drainage.flats.is_empty());
+    assert_eq!(
+        drainage.downstream[&Point { x: 0, y: 0 }],
+        Point { x: 1, y: 0 }
+    );
+    assert_eq!(
+        drainage.drains_to[&Point { x: 2, y: 2 }],
+        Point { x: 2, y: 2 }
+    );
+    assert_eq!(
+        drainage.drains_to[&Point { x: 4, y: 4 }],
+        Point { x: 6, y: 4 }
+    );
+    let total: usize = drainage
+        .sinks
+        .iter()
+        .map(|sink| drainage.accumulation[sink])
+        .sum();
+    assert_eq!(total, 50);
+
+    let map = Map::new("121\n555").unwrap();
+    let drainage = map.drainage();
+    assert_eq!(drainage.flats.len(), 0);
+    assert_eq!(drainage.ties, vec![Point { x: 1, y: 0 }]);
+    let map = Map::new("22\n23").unwrap();
+    assert_eq!(map.drainage().flats.len(), 3);
+}
@@ -1,51 +1,419 @@
+use std::fmt;
 use thiserror::Error;
 
 fn main() {
     let input = include_str!("../../inputs/day_10.txt");
-    println!("Part 1: {}", syntax_error_score(input).unwrap());
-    println!("Part 2: {}", middle_completion_score(input).unwrap());
+    let brackets = BracketSet::chunks();
+    if std::env::args().any(|arg| arg == "--diagnostics") {
+        for diagnostic in diagnostics(input, &brackets).unwrap() {
+            println!("{}", diagnostic);
+        }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--repair") {
+        for line in input.lines() {
+            let repair = repair(line, &brackets).unwrap();
+            if !repair.edits.is_empty() {
+                println!("{} ({} edits)", repair.repaired, repair.edits.len());
+            }
+        }
+        return;
+    }
+    let median = if std::env::args().any(|arg| arg == "--lower-median") {
+        Median::Lower
+    } else if std::env::args().any(|arg| arg == "--upper-median") {
+        Median::Upper
+    } else {
+        Median::Strict
+    };
+    println!("Part 1: {}", syntax_error_score(input, &brackets).unwrap());
+    println!(
+        "Part 2: {}",
+        middle_completion_score(input, &brackets, median).unwrap()
+    );
+}
+
+#[derive(Debug)]
+struct BracketSet {
+    pairs: Vec<Pair>,
+    completion_base: u64,
+}
+
+#[derive(Debug)]
+struct Pair {
+    open: String,
+    close: String,
+    corruption_score: u64,
+    completion_score: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+    pair: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+struct Diagnostic {
+    line_number: usize,
+    line: String,
+    problem: Problem,
+}
+
+#[derive(Debug)]
+enum Problem {
+    Corrupted {
+        column: usize,
+        found: String,
+        expected: Option<String>,
+        opened: Option<(usize, String)>,
+    },
+    Incomplete {
+        column: usize,
+        completion: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Median {
+    Strict,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug)]
+struct Token {
+    column: usize,
+    text: String,
+    open: Option<usize>,
+    close: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Choice {
+    Empty,
+    Delete,
+    Insert(usize),
+    Match(usize, usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Edit {
+    Insert {
+        column: usize,
+        text: String,
+    },
+    Delete {
+        column: usize,
+        text: String,
+    },
+    Replace {
+        column: usize,
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug)]
+struct Repair {
+    edits: Vec<Edit>,
+    repaired: String,
 }
 
-fn middle_completion_score(input: &str) -> Result<u64, ParseError> {
+impl BracketSet {
+    /// Completion scores are digits in `completion_base`, so each must be
+    /// below it for every completion to score differently.
+    fn new(
+        pairs: &[(&str, &str, u64, u64)],
+        completion_base: u64,
+    ) -> Result<BracketSet, ParseError> {
+        if let Some(&(open, _, _, score)) = pairs
+            .iter()
+            .find(|&&(_, _, _, score)| score >= completion_base)
+        {
+            return Err(ParseError::CompletionScoreTooLarge {
+                open: open.to_string(),
+                score,
+                base: completion_base,
+            });
+        }
+        Ok(BracketSet {
+            pairs: pairs
+                .iter()
+                .map(|&(open, close, corruption_score, completion_score)| Pair {
+                    open: open.to_string(),
+                    close: close.to_string(),
+                    corruption_score,
+                    completion_score,
+                })
+                .collect(),
+            completion_base,
+        })
+    }
+
+    fn chunks() -> BracketSet {
+        BracketSet::new(
+            &[
+                ("(", ")", 3, 1),
+                ("[", "]", 57, 2),
+                ("{", "}", 1197, 3),
+                ("<", ">", 25137, 4),
+            ],
+            5,
+        )
+        .expect("chunk completion scores are below the base")
+    }
+
+    fn delimiter(&self, s: &str) -> Option<(usize, &str)> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, pair)| [(i, pair.open.as_str()), (i, pair.close.as_str())])
+            .filter(|(_, delimiter)| !delimiter.is_empty() && s.starts_with(delimiter))
+            .max_by_key(|(_, delimiter)| delimiter.len())
+    }
+
+    fn pair(&self, i: usize) -> Result<&Pair, ParseError> {
+        self.pairs.get(i).ok_or(ParseError::UnknownPair(i))
+    }
+
+    fn score(&self, delimiter: &str) -> Result<u64, ParseError> {
+        self.pairs
+            .iter()
+            .find(|pair| pair.close == delimiter)
+            .map(|pair| pair.corruption_score)
+            .ok_or_else(|| ParseError::NoScoreDefined(delimiter.to_string()))
+    }
+}
+
+fn middle_completion_score(
+    input: &str,
+    brackets: &BracketSet,
+    median: Median,
+) -> Result<u64, ParseError> {
     let mut scores = Vec::new();
     for line in input.lines() {
-        match parse(line) {
-            Ok(stack) => scores.push(completion_score(&stack)),
+        match parse(line, brackets) {
+            Ok(stack) if stack.is_empty() => {}
+            Ok(stack) => scores.push(completion_score(&stack, brackets)?),
             Err(err) => match err {
-                ParseError::UnexpectedCharacter(_) => {}
+                ParseError::UnexpectedDelimiter { .. } => {}
                 _ => return Err(err),
             },
         }
     }
-    if scores.len() % 2 == 0 {
-        panic!("Didn't expect an even number of lines");
+    if scores.is_empty() {
+        return Err(ParseError::NoIncompleteLines);
     }
     scores.sort_unstable();
-    Ok(scores[scores.len() / 2])
-}
-
-fn completion_score(stack: &[char]) -> u64 {
-    let mut score = 0;
-    for c in stack.iter().rev() {
-        score *= 5;
-        score += match c {
-            '(' => 1,
-            '[' => 2,
-            '{' => 3,
-            '<' => 4,
-            _ => panic!("Did not expect {} to make it this far", c),
+    let middle = scores.len() / 2;
+    if scores.len() % 2 == 1 {
+        return Ok(scores[middle]);
+    }
+    match median {
+        Median::Strict => Err(ParseError::EvenNumberOfIncompleteLines(scores.len())),
+        Median::Lower => Ok(scores[middle - 1]),
+        Median::Upper => Ok(scores[middle]),
+    }
+}
+
+fn completion_score(stack: &[Open], brackets: &BracketSet) -> Result<u64, ParseError> {
+    let mut score: u64 = 0;
+    for open in stack.iter().rev() {
+        let pair = brackets.pair(open.pair)?;
+        score = score
+            .checked_mul(brackets.completion_base)
+            .and_then(|score| score.checked_add(pair.completion_score))
+            .ok_or(ParseError::ScoreOverflow)?;
+    }
+    Ok(score)
+}
+
+fn completion(stack: &[Open], brackets: &BracketSet) -> Result<String, ParseError> {
+    stack
+        .iter()
+        .rev()
+        .map(|open| brackets.pair(open.pair).map(|pair| pair.close.as_str()))
+        .collect()
+}
+
+fn diagnostics(input: &str, brackets: &BracketSet) -> Result<Vec<Diagnostic>, ParseError> {
+    let mut diagnostics = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let problem = match parse(line, brackets) {
+            Ok(stack) if stack.is_empty() => continue,
+            Ok(stack) => Problem::Incomplete {
+                column: line.chars().count() + 1,
+                completion: completion(&stack, brackets)?,
+            },
+            Err(ParseError::UnexpectedDelimiter {
+                column,
+                found,
+                expected,
+                opened,
+            }) => Problem::Corrupted {
+                column,
+                found,
+                expected,
+                opened,
+            },
+            Err(err) => return Err(err),
+        };
+        diagnostics.push(Diagnostic {
+            line_number: i + 1,
+            line: line.to_string(),
+            problem,
+        });
+    }
+    Ok(diagnostics)
+}
+
+fn tokenize(line: &str, brackets: &BracketSet) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+    let mut column = 1;
+    while let Some(c) = rest.chars().next() {
+        let token = match brackets.delimiter(rest) {
+            Some((i, delimiter)) => Token {
+                column,
+                text: delimiter.to_string(),
+                open: brackets
+                    .pairs
+                    .get(i)
+                    .filter(|pair| pair.open == delimiter)
+                    .map(|_| i),
+                close: brackets
+                    .pairs
+                    .get(i)
+                    .filter(|pair| pair.close == delimiter)
+                    .map(|_| i),
+            },
+            None => Token {
+                column,
+                text: c.to_string(),
+                open: None,
+                close: None,
+            },
+        };
+        rest = &rest[token.text.len()..];
+        column += token.text.chars().count();
+        tokens.push(token);
+    }
+    tokens
+}
+
+fn repair(line: &str, brackets: &BracketSet) -> Result<Repair, ParseError> {
+    let tokens = tokenize(line, brackets);
+    let n = tokens.len();
+    let mut cost = vec![vec![0; n + 1]; n + 1];
+    let mut choice = vec![vec![Choice::Empty; n + 1]; n + 1];
+    for length in 1..=n {
+        for i in 0..=(n - length) {
+            let j = i + length;
+            let token = &tokens[i];
+            let mut best = usize::MAX;
+            let mut best_choice = Choice::Empty;
+            if token.open.is_some() || token.close.is_some() {
+                for k in (i + 1)..j {
+                    if let Some((pair, replacements)) = pairing(token, &tokens[k]) {
+                        let total = replacements + cost[i + 1][k] + cost[k + 1][j];
+                        if total < best {
+                            best = total;
+                            best_choice = Choice::Match(k, pair);
+                        }
+                    }
+                }
+            }
+            if 1 + cost[i + 1][j] < best {
+                best = 1 + cost[i + 1][j];
+                best_choice = match token.open {
+                    Some(pair) => Choice::Insert(pair),
+                    None => Choice::Delete,
+                };
+            }
+            cost[i][j] = best;
+            choice[i][j] = best_choice;
+        }
+    }
+    let mut repair = Repair {
+        edits: Vec::new(),
+        repaired: String::new(),
+    };
+    let end = line.chars().count() + 1;
+    emit(&tokens, &choice, brackets, 0, n, end, &mut repair)?;
+    Ok(repair)
+}
+
+fn pairing(open: &Token, close: &Token) -> Option<(usize, usize)> {
+    if close.open.is_none() && close.close.is_none() {
+        return None;
+    }
+    match (open.open, close.close) {
+        (Some(a), Some(b)) if a == b => Some((a, 0)),
+        (Some(a), _) => Some((a, 1)),
+        (None, Some(b)) => Some((b, 1)),
+        (None, None) => close.open.map(|b| (b, 2)),
+    }
+}
+
+fn emit(
+    tokens: &[Token],
+    choice: &[Vec<Choice>],
+    brackets: &BracketSet,
+    i: usize,
+    j: usize,
+    end: usize,
+    repair: &mut Repair,
+) -> Result<(), ParseError> {
+    let column = |k: usize| tokens.get(k).map(|token| token.column).unwrap_or(end);
+    match choice[i][j] {
+        Choice::Empty => {}
+        Choice::Delete => {
+            repair.edits.push(Edit::Delete {
+                column: tokens[i].column,
+                text: tokens[i].text.clone(),
+            });
+            emit(tokens, choice, brackets, i + 1, j, end, repair)?;
+        }
+        Choice::Insert(pair) => {
+            let pair = brackets.pair(pair)?;
+            repair.repaired.push_str(&tokens[i].text);
+            emit(tokens, choice, brackets, i + 1, j, end, repair)?;
+            repair.repaired.push_str(&pair.close);
+            repair.edits.push(Edit::Insert {
+                column: column(j),
+                text: pair.close.clone(),
+            });
+        }
+        Choice::Match(k, pair) => {
+            let pair = brackets.pair(pair)?;
+            keep_or_replace(&tokens[i], &pair.open, repair);
+            emit(tokens, choice, brackets, i + 1, k, end, repair)?;
+            keep_or_replace(&tokens[k], &pair.close, repair);
+            emit(tokens, choice, brackets, k + 1, j, end, repair)?;
         }
     }
-    score
+    Ok(())
 }
 
-fn syntax_error_score(input: &str) -> Result<u64, ParseError> {
+fn keep_or_replace(token: &Token, text: &str, repair: &mut Repair) {
+    if token.text != text {
+        repair.edits.push(Edit::Replace {
+            column: token.column,
+            from: token.text.clone(),
+            to: text.to_string(),
+        });
+    }
+    repair.repaired.push_str(text);
+}
+
+fn syntax_error_score(input: &str, brackets: &BracketSet) -> Result<u64, ParseError> {
     let mut total = 0;
     for line in input.lines() {
-        match parse(line) {
+        match parse(line, brackets) {
             Ok(_) => {}
             Err(err) => match err {
-                ParseError::UnexpectedCharacter(c) => total += score(c)?,
+                ParseError::UnexpectedDelimiter { found, .. } => total += brackets.score(&found)?,
                 _ => return Err(err),
             },
         }
@@ -53,54 +421,115 @@ fn syntax_error_score(input: &str) -> Result<u64, ParseError> {
     Ok(total)
 }
 
-fn parse(line: &str) -> Result<Vec<char>, ParseError> {
-    let mut stack = Vec::new();
-    for c in line.chars() {
-        let ok = match c {
-            '(' | '[' | '{' | '<' => {
-                stack.push(c);
-                true
-            }
-            ')' => maybe_pop(&mut stack, '('),
-            ']' => maybe_pop(&mut stack, '['),
-            '}' => maybe_pop(&mut stack, '{'),
-            '>' => maybe_pop(&mut stack, '<'),
-            _ => return Err(ParseError::InvalidCharacter(c)),
-        };
-        if !ok {
-            return Err(ParseError::UnexpectedCharacter(c));
+fn parse(line: &str, brackets: &BracketSet) -> Result<Vec<Open>, ParseError> {
+    let mut stack: Vec<Open> = Vec::new();
+    let mut rest = line;
+    let mut column = 1;
+    while let Some(c) = rest.chars().next() {
+        let (i, delimiter) = brackets
+            .delimiter(rest)
+            .ok_or(ParseError::InvalidCharacter(c))?;
+        let pair = brackets.pair(i)?;
+        if delimiter == pair.close && stack.last().map(|open| open.pair) == Some(i) {
+            stack.pop();
+        } else if delimiter == pair.open {
+            stack.push(Open { pair: i, column });
+        } else {
+            let last = match stack.last() {
+                Some(open) => Some((open.column, brackets.pair(open.pair)?)),
+                None => None,
+            };
+            return Err(ParseError::UnexpectedDelimiter {
+                column,
+                found: delimiter.to_string(),
+                expected: last.map(|(_, pair)| pair.close.clone()),
+                opened: last.map(|(column, pair)| (column, pair.open.clone())),
+            });
         }
+        rest = &rest[delimiter.len()..];
+        column += delimiter.chars().count();
     }
     Ok(stack)
 }
 
-fn maybe_pop(stack: &mut Vec<char>, c: char) -> bool {
-    if stack.last().map(|&last| last == c).unwrap_or(false) {
-        stack.pop();
-        true
-    } else {
-        false
-    }
-}
-
-fn score(c: char) -> Result<u64, ParseError> {
-    match c {
-        ')' => Ok(3),
-        ']' => Ok(57),
-        '}' => Ok(1197),
-        '>' => Ok(25137),
-        _ => Err(ParseError::NoScoreDefined(c)),
-    }
-}
-
 #[derive(Error, Debug)]
 enum ParseError {
-    #[error("no score is defined for char: {0}")]
-    NoScoreDefined(char),
-    #[error("unexpected character: {0}")]
-    UnexpectedCharacter(char),
+    #[error("no score is defined for delimiter: {0}")]
+    NoScoreDefined(String),
+    #[error("unexpected delimiter at column {column}: {found}")]
+    UnexpectedDelimiter {
+        column: usize,
+        found: String,
+        expected: Option<String>,
+        opened: Option<(usize, String)>,
+    },
     #[error("invalid character: {0}")]
     InvalidCharacter(char),
+    #[error("no bracket pair with index: {0}")]
+    UnknownPair(usize),
+    #[error("completion score {score} for {open} is not below the base {base}")]
+    CompletionScoreTooLarge { open: String, score: u64, base: u64 },
+    #[error("completion score overflowed")]
+    ScoreOverflow,
+    #[error("no incomplete lines")]
+    NoIncompleteLines,
+    #[error("no middle score for an even number of incomplete lines: {0}")]
+    EvenNumberOfIncompleteLines(usize),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indent = |column: usize| " ".repeat(column - 1);
+        match &self.problem {
+            Problem::Corrupted {
+                column,
+                found,
+                expected,
+                opened,
+            } => {
+                match expected {
+                    Some(expected) => {
+                        writeln!(f, "error: expected `{}`, found `{}`", expected, found)?
+                    }
+                    None => writeln!(f, "error: unexpected `{}`", found)?,
+                }
+                writeln!(f, " --> line {}, column {}", self.line_number, column)?;
+                writeln!(f, "  |")?;
+                writeln!(f, "  | {}", self.line)?;
+                let carets = "^".repeat(found.chars().count());
+                match (expected, opened) {
+                    (Some(expected), Some((opened_at, opener))) => {
+                        let width = opener.chars().count();
+                        writeln!(
+                            f,
+                            "  | {}{}{}{} expected `{}`",
+                            indent(*opened_at),
+                            "-".repeat(width),
+                            " ".repeat(column - opened_at - width),
+                            carets,
+                            expected
+                        )?;
+                        writeln!(f, "  | {}|", indent(*opened_at))?;
+                        writeln!(
+                            f,
+                            "  | {}unclosed `{}` opened here",
+                            indent(*opened_at),
+                            opener
+                        )?;
+                    }
+                    _ => writeln!(f, "  | {}{} no matching opener", indent(*column), carets)?,
+                }
+            }
+            Problem::Incomplete { column, completion } => {
+                writeln!(f, "error: incomplete line, expected `{}`", completion)?;
+                writeln!(f, " --> line {}, column {}", self.line_number, column)?;
+                writeln!(f, "  |")?;
+                writeln!(f, "  | {}", self.line)?;
+                writeln!(f, "  | {}^ insert `{}`", indent(*column), completion)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[test]
@@ -115,6 +544,159 @@ fn example() {
 [<(<(<(<{}))><([]([]()
 <{([([[(<>()){}]>(<<{{
 <{([{{}}[<[[[<>{}]]]>[]]";
-    assert_eq!(syntax_error_score(input).unwrap(), 26397);
-    assert_eq!(middle_completion_score(input).unwrap(), 288957);
+    let brackets = BracketSet::chunks();
+    assert_eq!(syntax_error_score(input, &brackets).unwrap(), 26397);
+    assert_eq!(
+        middle_completion_score(input, &brackets, Median::Strict).unwrap(),
+        288957
+    );
+}
+
+#[test]
+fn bracket_sets() {
+    let brackets = BracketSet::new(
+        &[("(", ")", 1, 1), ("\"", "\"", 2, 2), ("<b>", "</b>", 3, 3)],
+        4,
+    )
+    .unwrap();
+    assert!(parse("(\"\")<b></b>", &brackets).unwrap().is_empty());
+    assert!(matches!(
+        parse("(<b>\"</b>", &brackets),
+        Err(ParseError::UnexpectedDelimiter { found, .. }) if found == "</b>"
+    ));
+    assert!(matches!(
+        parse("(x)", &brackets),
+        Err(ParseError::InvalidCharacter('x'))
+    ));
+    let stack = parse("(<b>\"", &brackets).unwrap();
+    assert_eq!(completion_score(&stack, &brackets).unwrap(), 45);
+    assert_eq!(syntax_error_score("(<b>)\n(</b>", &brackets).unwrap(), 4);
+    let brackets = BracketSet::new(&[("(", ")", 1, 1), ("[", "]", 2, 2)], 10).unwrap();
+    let stack = parse("([", &brackets).unwrap();
+    assert_eq!(completion_score(&stack, &brackets).unwrap(), 21);
+    assert!(matches!(
+        BracketSet::new(&[("(", ")", 1, 1), ("[", "]", 2, 3)], 3),
+        Err(ParseError::CompletionScoreTooLarge {
+            score: 3,
+            base: 3,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn diagnostics_example() {
+    let brackets = BracketSet::chunks();
+    let input = "[({(<(())[]>[[{[]{<()<>>\n{([(<{}[<>[]}>{[]{[(<()>\n())\n()";
+    let diagnostics = diagnostics(input, &brackets).unwrap();
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "error: incomplete line, expected `}}]])})]`
+ --> line 1, column 25
+  |
+  | [({(<(())[]>[[{[]{<()<>>
+  |                         ^ insert `}}]])})]`
+"
+    );
+    assert_eq!(
+        diagnostics[1].to_string(),
+        "error: expected `]`, found `}`
+ --> line 2, column 13
+  |
+  | {([(<{}[<>[]}>{[]{[(<()>
+  |        -    ^ expected `]`
+  |        |
+  |        unclosed `[` opened here
+"
+    );
+    assert_eq!(
+        diagnostics[2].to_string(),
+        "error: unexpected `)`
+ --> line 3, column 3
+  |
+  | ())
+  |   ^ no matching opener
+"
+    );
+}
+
+#[test]
+fn repairs() {
+    let brackets = BracketSet::chunks();
+    let fixed = repair("(]", &brackets).unwrap();
+    assert_eq!(fixed.repaired, "()");
+    assert_eq!(
+        fixed.edits,
+        vec![Edit::Replace {
+            column: 2,
+            from: "]".to_string(),
+            to: ")".to_string()
+        }]
+    );
+    let fixed = repair("())", &brackets).unwrap();
+    assert_eq!(fixed.repaired, "()");
+    assert_eq!(
+        fixed.edits,
+        vec![Edit::Delete {
+            column: 3,
+            text: ")".to_string()
+        }]
+    );
+    let fixed = repair("[(", &brackets).unwrap();
+    assert_eq!(fixed.repaired, "[]");
+    let fixed = repair("[", &brackets).unwrap();
+    assert_eq!(fixed.repaired, "[]");
+    assert_eq!(
+        fixed.edits,
+        vec![Edit::Insert {
+            column: 2,
+            text: "]".to_string()
+        }]
+    );
+    let fixed = repair("(x)", &brackets).unwrap();
+    assert_eq!(fixed.repaired, "()");
+
+    let incomplete = "[({(<(())[]>[[{[]{<()<>>";
+    let fixed = repair(incomplete, &brackets).unwrap();
+    assert!(parse(&fixed.repaired, &brackets).unwrap().is_empty());
+    assert!(fixed.edits.len() <= "}}]])})]".len());
+    let corrupted = "{([(<{}[<>[]}>{[]{[(<()>";
+    let fixed = repair(corrupted, &brackets).unwrap();
+    assert!(parse(&fixed.repaired, &brackets).unwrap().is_empty());
+    assert_eq!(fixed.edits.len(), 5);
+}
+
+#[test]
+fn middle_scores() {
+    let brackets = BracketSet::chunks();
+    let input = "(\n[\n{\n<\n()";
+    assert!(matches!(
+        middle_completion_score(input, &brackets, Median::Strict),
+        Err(ParseError::EvenNumberOfIncompleteLines(4))
+    ));
+    assert_eq!(
+        middle_completion_score(input, &brackets, Median::Lower).unwrap(),
+        2
+    );
+    assert_eq!(
+        middle_completion_score(input, &brackets, Median::Upper).unwrap(),
+        3
+    );
+    assert!(matches!(
+        middle_completion_score("()\n(]", &brackets, Median::Lower),
+        Err(ParseError::NoIncompleteLines)
+    ));
+    assert!(matches!(
+        completion_score(&[Open { pair: 4, column: 1 }], &brackets),
+        Err(ParseError::UnknownPair(4))
+    ));
+    assert!(matches!(
+        completion(&[Open { pair: 4, column: 1 }], &brackets),
+        Err(ParseError::UnknownPair(4))
+    ));
+    assert!(matches!(
+        completion_score(&parse(&"(".repeat(100), &brackets).unwrap(), &brackets),
+        Err(ParseError::ScoreOverflow)
+    ));
 }
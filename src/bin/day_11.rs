@@ -1,39 +1,98 @@
 use anyhow::{anyhow, Error};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fmt,
+    fs::File,
+    io::{self, Write},
+    str::FromStr,
+    thread,
+    time::Duration,
 };
 
 fn main() {
     let input = include_str!("../../inputs/day_11.txt");
-    let mut map = Map::new(input).unwrap();
-    map.step(100);
-    println!("Part 1: {}", map.number_of_flashes());
-    let mut map = Map::new(input).unwrap();
-    println!("Part 2: {}", map.first_step_when_all_flash());
+    if std::env::args().any(|arg| arg == "--play") {
+        let mut dump = if std::env::args().any(|arg| arg == "--dump") {
+            Some(File::create("day_11_frames.txt").unwrap())
+        } else {
+            None
+        };
+        let mut automaton = Automaton::new(input, Rule::octopus()).unwrap();
+        play(
+            &mut automaton,
+            100,
+            10,
+            &mut io::stdout(),
+            dump.as_mut().map(|file| file as &mut dyn Write),
+        )
+        .unwrap();
+        return;
+    }
+    let mut automaton = Automaton::new(input, Rule::octopus()).unwrap();
+    automaton.step(100);
+    println!("Part 1: {}", automaton.number_of_flashes());
+    let mut automaton = Automaton::new(input, Rule::octopus()).unwrap();
+    println!("Part 2: {}", automaton.first_step_when_all_flash().unwrap());
 }
 
 #[derive(Debug)]
-struct Map {
-    map: HashMap<(i64, i64), u32>,
+struct Automaton {
+    width: usize,
+    height: usize,
+    energy: Vec<u32>,
+    flashed: Vec<bool>,
     number_of_flashes: usize,
+    rule: Rule,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    neighborhood: Neighborhood,
+    edges: Edges,
+    threshold: u32,
+    reset: u32,
+}
+
+#[derive(Debug, Clone)]
+enum Neighborhood {
+    Moore,
+    VonNeumann,
+    Custom(Vec<(i64, i64)>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Edges {
+    Bounded,
+    Wrap,
 }
 
-impl Map {
-    fn new(input: &str) -> Result<Map, Error> {
-        let mut map = HashMap::new();
-        for (y, line) in input.lines().enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                map.insert(
-                    (x.try_into()?, y.try_into()?),
+impl Automaton {
+    fn new(input: &str, rule: Rule) -> Result<Automaton, Error> {
+        let mut width = None;
+        let mut energy = Vec::new();
+        for line in input.lines() {
+            let row = line
+                .chars()
+                .map(|c| {
                     c.to_digit(10)
-                        .ok_or_else(|| anyhow!("Unexpected digit: {}", c))?,
-                );
+                        .ok_or_else(|| anyhow!("Unexpected digit: {}", c))
+                })
+                .collect::<Result<Vec<u32>, Error>>()?;
+            if *width.get_or_insert(row.len()) != row.len() {
+                return Err(anyhow!("Ragged line: {}", line));
             }
+            energy.extend(row);
         }
-        Ok(Map {
-            map,
+        let width = width
+            .filter(|&width| width > 0)
+            .ok_or_else(|| anyhow!("Empty automaton"))?;
+        Ok(Automaton {
+            width,
+            height: energy.len() / width,
+            flashed: vec![false; energy.len()],
+            energy,
             number_of_flashes: 0,
+            rule,
         })
     }
 
@@ -43,94 +102,189 @@ impl Map {
         }
     }
 
-    fn first_step_when_all_flash(&mut self) -> usize {
+    fn first_step_when_all_flash(&mut self) -> Result<usize, Error> {
+        let mut seen = HashMap::new();
         let mut steps = 0;
-        let number_of_octopodes = self.map.keys().len();
         loop {
+            if let Some(first) = seen.insert(self.energy.clone(), steps) {
+                return Err(anyhow!(
+                    "Octopodes never all flash: the state after step {} repeats with period {}",
+                    first,
+                    steps - first
+                ));
+            }
             steps += 1;
-            if self.step_one() == number_of_octopodes {
-                return steps;
+            if self.step_one() == self.energy.len() {
+                return Ok(steps);
             }
         }
     }
 
     fn step_one(&mut self) -> usize {
-        for value in self.map.values_mut() {
+        let mut queue = Vec::new();
+        for (i, value) in self.energy.iter_mut().enumerate() {
             *value += 1;
+            if *value > self.rule.threshold {
+                queue.push(i);
+            }
         }
-        let mut flashes = HashSet::new();
-        while self.flash(&mut flashes) {}
-        self.number_of_flashes += flashes.len();
-        for key in &flashes {
-            let value = self.map.get_mut(key).unwrap();
-            *value = 0;
+        self.flashed.iter_mut().for_each(|flashed| *flashed = false);
+        let mut flashes = 0;
+        while let Some(i) = queue.pop() {
+            if self.flashed[i] {
+                continue;
+            }
+            self.flashed[i] = true;
+            flashes += 1;
+            for neighbor in self.neighbors(i) {
+                self.energy[neighbor] += 1;
+                if self.energy[neighbor] > self.rule.threshold && !self.flashed[neighbor] {
+                    queue.push(neighbor);
+                }
+            }
         }
-        flashes.len()
+        for (value, &flashed) in self.energy.iter_mut().zip(&self.flashed) {
+            if flashed {
+                *value = self.rule.reset;
+            }
+        }
+        self.number_of_flashes += flashes;
+        flashes
+    }
+
+    fn neighbors(&self, i: usize) -> Vec<usize> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x, y) = ((i % self.width) as i64, (i / self.width) as i64);
+        self.rule
+            .neighborhood
+            .offsets()
+            .into_iter()
+            .filter_map(|(dx, dy)| {
+                let (x, y) = match self.rule.edges {
+                    Edges::Bounded => (x + dx, y + dy),
+                    Edges::Wrap => ((x + dx).rem_euclid(width), (y + dy).rem_euclid(height)),
+                };
+                if x >= 0 && x < width && y >= 0 && y < height {
+                    Some((y * width + x) as usize)
+                } else {
+                    None
+                }
+            })
+            .collect()
     }
 
-    fn flash(&mut self, flashes: &mut HashSet<(i64, i64)>) -> bool {
-        let mut flashed = false;
-        let mut to_increase = Vec::new();
-        for (key, value) in self.map.iter() {
-            if *value > 9 && flashes.insert(*key) {
-                flashed = true;
-                to_increase.extend(self.neighbors(*key));
+    fn number_of_flashes(&self) -> usize {
+        self.number_of_flashes
+    }
+
+    fn frame(&self, color: bool) -> String {
+        let mut frame = String::new();
+        for (y, row) in self.energy.chunks(self.width).enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                if color && self.flashed[y * self.width + x] {
+                    frame.push_str(&format!("\x1b[1;33m{}\x1b[0m", value));
+                } else {
+                    frame.push_str(&value.to_string());
+                }
             }
+            frame.push('\n');
         }
-        for key in to_increase {
-            let value = self.map.get_mut(&key).unwrap();
-            *value += 1;
+        frame
+    }
+}
+
+impl Rule {
+    fn octopus() -> Rule {
+        Rule {
+            neighborhood: Neighborhood::Moore,
+            edges: Edges::Bounded,
+            threshold: 9,
+            reset: 0,
         }
-        flashed
     }
+}
 
-    fn neighbors(&self, (x, y): (i64, i64)) -> impl Iterator<Item = (i64, i64)> + '_ {
-        [
-            (x - 1, y - 1),
-            (x - 1, y),
-            (x - 1, y + 1),
-            (x, y - 1),
-            (x, y + 1),
-            (x + 1, y - 1),
-            (x + 1, y),
-            (x + 1, y + 1),
-        ]
-        .into_iter()
-        .filter(|key| self.map.contains_key(key))
+impl Neighborhood {
+    fn offsets(&self) -> Vec<(i64, i64)> {
+        match self {
+            Neighborhood::Moore => vec![
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+            Neighborhood::VonNeumann => vec![(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighborhood::Custom(offsets) => offsets.clone(),
+        }
     }
+}
 
-    fn number_of_flashes(&self) -> usize {
-        self.number_of_flashes
+impl FromStr for Neighborhood {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Neighborhood, Error> {
+        match s {
+            "moore" => Ok(Neighborhood::Moore),
+            "von-neumann" => Ok(Neighborhood::VonNeumann),
+            _ => s
+                .split(';')
+                .map(|offset| {
+                    let (dx, dy) = offset
+                        .split_once(',')
+                        .ok_or_else(|| anyhow!("Invalid offset: {}", offset))?;
+                    Ok((dx.parse()?, dy.parse()?))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .map(Neighborhood::Custom),
+        }
     }
 }
 
-impl fmt::Display for Map {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut min_x = i64::MAX;
-        let mut max_x = i64::MIN;
-        let mut min_y = i64::MAX;
-        let mut max_y = i64::MIN;
-        for &(x, y) in self.map.keys() {
-            if x < min_x {
-                min_x = x;
-            }
-            if x > max_x {
-                max_x = x;
-            }
-            if y < min_y {
-                min_y = y;
-            }
-            if y > max_y {
-                max_y = y;
-            }
+impl FromStr for Edges {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Edges, Error> {
+        match s {
+            "bounded" => Ok(Edges::Bounded),
+            "wrap" => Ok(Edges::Wrap),
+            _ => Err(anyhow!("Invalid edges: {}", s)),
         }
-        for y in min_y..=max_y {
-            for x in min_x..=max_x {
-                write!(f, "{}", self.map[&(x, y)])?
-            }
-            writeln!(f)?;
+    }
+}
+
+fn play(
+    automaton: &mut Automaton,
+    steps: usize,
+    fps: u32,
+    terminal: &mut dyn Write,
+    mut dump: Option<&mut dyn Write>,
+) -> io::Result<()> {
+    for step in 0..=steps {
+        if step > 0 {
+            automaton.step_one();
+        }
+        write!(
+            terminal,
+            "\x1b[2J\x1b[HStep {}\n{}",
+            step,
+            automaton.frame(true)
+        )?;
+        terminal.flush()?;
+        if let Some(dump) = dump.as_mut() {
+            writeln!(dump, "Step {}\n{}", step, automaton.frame(false))?;
         }
-        Ok(())
+        if fps > 0 {
+            thread::sleep(Duration::from_secs(1) / fps);
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Automaton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.frame(false))
     }
 }
 
@@ -146,9 +300,93 @@ fn example() {
 6882881134
 4846848554
 5283751526";
-    let mut map = Map::new(input).unwrap();
-    map.step(100);
-    assert_eq!(map.number_of_flashes(), 1656);
-    let mut map = Map::new(input).unwrap();
-    assert_eq!(map.first_step_when_all_flash(), 195);
+    let mut automaton = Automaton::new(input, Rule::octopus()).unwrap();
+    automaton.step(100);
+    assert_eq!(automaton.number_of_flashes(), 1656);
+    let mut automaton = Automaton::new(input, Rule::octopus()).unwrap();
+    assert_eq!(automaton.first_step_when_all_flash().unwrap(), 195);
+}
+
+#[test]
+fn never_synchronizes() {
+    let mut automaton = Automaton::new("002", Rule::octopus()).unwrap();
+    assert!(automaton.first_step_when_all_flash().is_err());
+    assert!(Automaton::new("12\n3", Rule::octopus()).is_err());
+    assert!(Automaton::new("", Rule::octopus()).is_err());
+    assert!(Automaton::new("\n", Rule::octopus()).is_err());
+    assert!(Automaton::new("\n\n\n", Rule::octopus()).is_err());
+}
+
+#[test]
+fn playback() {
+    let mut automaton =
+        Automaton::new("11111\n19991\n19191\n19991\n11111", Rule::octopus()).unwrap();
+    let mut terminal = Vec::new();
+    let mut dump = Vec::new();
+    play(&mut automaton, 2, 0, &mut terminal, Some(&mut dump)).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert_eq!(
+        dump,
+        "Step 0
+11111
+19991
+19191
+19991
+11111
+
+Step 1
+34543
+40004
+50005
+40004
+34543
+
+Step 2
+45654
+51115
+61116
+51115
+45654
+
+"
+    );
+    let terminal = String::from_utf8(terminal).unwrap();
+    assert_eq!(terminal.matches("\x1b[2J").count(), 3);
+    assert_eq!(terminal.matches("\x1b[1;33m0\x1b[0m").count(), 9);
+    assert_eq!(automaton.to_string(), "45654\n51115\n61116\n51115\n45654\n");
+}
+
+#[test]
+fn rules() {
+    let rule = Rule {
+        neighborhood: Neighborhood::VonNeumann,
+        ..Rule::octopus()
+    };
+    let mut automaton = Automaton::new("000\n090\n000", rule).unwrap();
+    automaton.step_one();
+    assert_eq!(automaton.to_string(), "121\n202\n121\n");
+    let mut automaton = Automaton::new("000\n090\n000", Rule::octopus()).unwrap();
+    automaton.step_one();
+    assert_eq!(automaton.to_string(), "222\n202\n222\n");
+
+    let rule = Rule {
+        neighborhood: Neighborhood::VonNeumann,
+        edges: Edges::Wrap,
+        threshold: 3,
+        reset: 1,
+    };
+    let mut automaton = Automaton::new("0003", rule).unwrap();
+    automaton.step_one();
+    assert_eq!(automaton.to_string(), "2121\n");
+    assert_eq!(automaton.number_of_flashes(), 1);
+
+    let rule = Rule {
+        neighborhood: "1,0".parse().unwrap(),
+        ..Rule::octopus()
+    };
+    let mut automaton = Automaton::new("9000", rule).unwrap();
+    automaton.step_one();
+    assert_eq!(automaton.to_string(), "0211\n");
+    assert!("wrap".parse::<Edges>().is_ok());
+    assert!("1,x".parse::<Neighborhood>().is_err());
 }
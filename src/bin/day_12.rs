@@ -1,17 +1,44 @@
 use anyhow::{anyhow, Error};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use thiserror::Error;
 
 fn main() {
     let input = include_str!("../../inputs/day_12.txt");
-    println!("Part 1: {}", paths(input, false).unwrap().len());
-    println!("Part 2: {}", paths(input, true).unwrap().len());
+    if std::env::args().any(|arg| arg == "--dot") {
+        let caves = caves(input).unwrap();
+        print!("{}", dot(&caves, None));
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--paths") {
+        let policy = if std::env::args().any(|arg| arg == "--part-2") {
+            Policy::part_2()
+        } else {
+            Policy::part_1()
+        };
+        for path in paths(input, &policy).unwrap() {
+            println!("{}", path.visited.join(","));
+        }
+        return;
+    }
+    println!("Part 1: {}", count_paths(input, &Policy::part_1()).unwrap());
+    println!("Part 2: {}", count_paths(input, &Policy::part_2()).unwrap());
 }
 
-fn paths(input: &str, part_2: bool) -> Result<Vec<Path>, Error> {
-    let caves = caves(input)?;
+fn count_paths(input: &str, policy: &Policy) -> Result<u64, Error> {
+    let caves = validated_caves(input, policy)?;
+    if !policy.required.iter().all(|name| caves.contains_key(name)) {
+        return Ok(0);
+    }
+    let graph = Graph::new(&caves, policy)?;
+    let visits = graph.visit(0, graph.start);
+    Ok(graph.count(policy, graph.start, visits, 0, &mut HashMap::new()))
+}
+
+fn paths(input: &str, policy: &Policy) -> Result<Vec<Path>, Error> {
+    let caves = validated_caves(input, policy)?;
     let start = caves.get("start").ok_or(anyhow!("No cave named 'start'"))?;
-    let path = Path::new(start.name.as_str(), part_2);
-    let paths = find_paths(&caves, path);
+    let path = Path::new(start.name.as_str());
+    let paths = find_paths(&caves, path, policy);
     Ok(paths)
 }
 
@@ -32,21 +59,150 @@ fn caves(input: &str) -> Result<HashMap<String, Cave>, Error> {
     Ok(caves)
 }
 
-fn find_paths(caves: &HashMap<String, Cave>, path: Path) -> Vec<Path> {
+fn validated_caves(input: &str, policy: &Policy) -> Result<HashMap<String, Cave>, Error> {
+    let caves = caves(input)?;
+    let problems = validate(&caves, policy);
+    if problems.is_empty() {
+        Ok(caves)
+    } else {
+        Err(anyhow!(
+            "Invalid cave system: {}",
+            problems
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        ))
+    }
+}
+
+/// Big caves the policy limits can't loop forever, so only edges between two
+/// unlimited big caves are reported.
+fn validate(caves: &HashMap<String, Cave>, policy: &Policy) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for name in ["start", "end"] {
+        if !caves.contains_key(name) {
+            problems.push(Problem::MissingCave(name.to_string()));
+        }
+    }
+    let mut names: Vec<&String> = caves.keys().collect();
+    names.sort_unstable();
+    let unbounded = |name: &str| !is_small(name) && !policy.limits.contains_key(name);
+    let mut edges = BTreeSet::new();
+    for name in &names {
+        for other in &caves[*name].connections {
+            if unbounded(name) && unbounded(other) {
+                edges.insert(((*name).min(other), (*name).max(other)));
+            }
+        }
+    }
+    for (name, other) in edges {
+        problems.push(Problem::AdjacentBigCaves(
+            name.to_string(),
+            other.to_string(),
+        ));
+    }
+    if caves.contains_key("start") {
+        let mut reachable = HashSet::from(["start"]);
+        let mut stack = vec!["start"];
+        while let Some(name) = stack.pop() {
+            for other in &caves[name].connections {
+                if reachable.insert(other.as_str()) {
+                    stack.push(other);
+                }
+            }
+        }
+        for name in names {
+            if !reachable.contains(name.as_str()) {
+                problems.push(Problem::Unreachable(name.to_string()));
+            }
+        }
+    }
+    problems
+}
+
+fn dot(caves: &HashMap<String, Cave>, path: Option<&Path>) -> String {
+    let mut highlighted = HashSet::new();
+    if let Some(path) = path {
+        for (a, b) in path.visited.iter().zip(path.visited.iter().skip(1)) {
+            highlighted.insert((a.min(b), a.max(b)));
+        }
+    }
+    let mut names: Vec<&String> = caves.keys().collect();
+    names.sort_unstable();
+    let mut dot = String::from("graph caves {\n");
+    for name in &names {
+        let shape = if is_small(name) {
+            "shape=ellipse"
+        } else {
+            "shape=box, style=filled, fillcolor=lightgray"
+        };
+        let color = if path.map(|path| path.visits(name) > 0).unwrap_or(false) {
+            ", color=red"
+        } else {
+            ""
+        };
+        dot.push_str(&format!("    \"{}\" [{}{}];\n", name, shape, color));
+    }
+    for name in &names {
+        let mut connections: Vec<&String> = caves[*name]
+            .connections
+            .iter()
+            .filter(|&other| *name < other)
+            .collect();
+        connections.sort_unstable();
+        for other in connections {
+            let style = if highlighted.contains(&(*name, other)) {
+                " [color=red, penwidth=2]"
+            } else {
+                ""
+            };
+            dot.push_str(&format!("    \"{}\" -- \"{}\"{};\n", name, other, style));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn find_paths(caves: &HashMap<String, Cave>, path: Path, policy: &Policy) -> Vec<Path> {
     let mut new_paths = vec![];
     let last = path.last();
     if last == "end" {
-        new_paths.push(path);
+        if policy.is_complete(&path) {
+            new_paths.push(path);
+        }
     } else {
         for connection in &caves.get(last).unwrap().connections {
-            if let Ok(path) = path.with(connection) {
-                new_paths.extend(find_paths(caves, path));
+            if let Ok(path) = path.with(connection, policy) {
+                new_paths.extend(find_paths(caves, path, policy));
             }
         }
     }
     new_paths
 }
 
+#[derive(Error, Debug, PartialEq, Eq)]
+enum Problem {
+    #[error("no cave named '{0}'")]
+    MissingCave(String),
+    #[error("adjacent big caves allow infinite paths: {0}-{1}")]
+    AdjacentBigCaves(String, String),
+    #[error("cave is unreachable from start: {0}")]
+    Unreachable(String),
+}
+
+#[derive(Debug)]
+struct Graph {
+    names: Vec<String>,
+    small: Vec<bool>,
+    caps: Vec<u64>,
+    places: Vec<u64>,
+    required: Vec<usize>,
+    connections: Vec<Vec<usize>>,
+    start: usize,
+    end: usize,
+}
+
 #[derive(Debug)]
 struct Cave {
     name: String,
@@ -56,9 +212,16 @@ struct Cave {
 #[derive(Clone, Debug)]
 struct Path {
     visited: Vec<String>,
-    seen: HashSet<String>,
-    part_2: bool,
-    has_visited_small_room_twice: bool,
+    visits: HashMap<String, usize>,
+    small_caves_visited_twice: usize,
+}
+
+#[derive(Debug, Default)]
+struct Policy {
+    limits: HashMap<String, usize>,
+    twice_budget: usize,
+    forbidden: HashSet<String>,
+    required: HashSet<String>,
 }
 
 impl Cave {
@@ -70,13 +233,100 @@ impl Cave {
     }
 }
 
+impl Graph {
+    /// Each cave's capped visit count is packed into one digit of a mixed
+    /// radix `u64`, so the visited state is a single integer.
+    fn new(caves: &HashMap<String, Cave>, policy: &Policy) -> Result<Graph, Error> {
+        let mut names: Vec<&str> = caves.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        let id = |name: &str| names.iter().position(|&other| other == name);
+        let start = id("start").ok_or(anyhow!("No cave named 'start'"))?;
+        let end = id("end").ok_or(anyhow!("No cave named 'end'"))?;
+        let caps = names
+            .iter()
+            .map(|name| u64::try_from(policy.distinct_visits(name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut places = Vec::new();
+        let mut place: Option<u64> = Some(1);
+        for &cap in &caps {
+            let current = place.ok_or(anyhow!("Too many caves to count: {}", caves.len()))?;
+            places.push(current);
+            place = cap
+                .checked_add(1)
+                .and_then(|radix| current.checked_mul(radix));
+        }
+        Ok(Graph {
+            small: names.iter().map(|name| is_small(name)).collect(),
+            caps,
+            places,
+            required: policy.required.iter().filter_map(|name| id(name)).collect(),
+            connections: names
+                .iter()
+                .map(|&name| {
+                    caves[name]
+                        .connections
+                        .iter()
+                        .filter_map(|connection| id(connection))
+                        .collect()
+                })
+                .collect(),
+            names: names.iter().map(|name| name.to_string()).collect(),
+            start,
+            end,
+        })
+    }
+
+    fn visits(&self, visited: u64, cave: usize) -> u64 {
+        visited / self.places[cave] % (self.caps[cave] + 1)
+    }
+
+    fn visit(&self, visited: u64, cave: usize) -> u64 {
+        if self.visits(visited, cave) < self.caps[cave] {
+            visited + self.places[cave]
+        } else {
+            visited
+        }
+    }
+
+    fn count(
+        &self,
+        policy: &Policy,
+        cave: usize,
+        visited: u64,
+        visited_twice: usize,
+        memo: &mut HashMap<(usize, u64, usize), u64>,
+    ) -> u64 {
+        if cave == self.end {
+            let complete = self.required.iter().all(|&id| self.visits(visited, id) > 0);
+            return complete as u64;
+        }
+        if let Some(&count) = memo.get(&(cave, visited, visited_twice)) {
+            return count;
+        }
+        let mut count = 0;
+        for &next in &self.connections[cave] {
+            let visits = self.visits(visited, next) as usize;
+            if !policy.allows_visit(&self.names[next], visits, visited_twice) {
+                continue;
+            }
+            let visited_twice = if self.small[next] && visits == 1 {
+                (visited_twice + 1).min(policy.twice_budget)
+            } else {
+                visited_twice
+            };
+            count += self.count(policy, next, self.visit(visited, next), visited_twice, memo);
+        }
+        memo.insert((cave, visited, visited_twice), count);
+        count
+    }
+}
+
 impl Path {
-    fn new<S: ToString>(name: S, part_2: bool) -> Path {
+    fn new<S: ToString>(name: S) -> Path {
         Path {
             visited: vec![name.to_string()],
-            seen: HashSet::from([name.to_string()]),
-            part_2,
-            has_visited_small_room_twice: false,
+            visits: HashMap::from([(name.to_string(), 1)]),
+            small_caves_visited_twice: 0,
         }
     }
 
@@ -84,33 +334,81 @@ impl Path {
         self.visited.last().unwrap()
     }
 
-    fn with(&self, name: &str) -> Result<Path, Error> {
-        if (self.part_2 && self.is_illegal_part_2_path(name))
-            || (!self.part_2 && self.is_illegal_part_1_path(name))
-        {
+    fn visits(&self, name: &str) -> usize {
+        self.visits.get(name).cloned().unwrap_or(0)
+    }
+
+    fn with(&self, name: &str, policy: &Policy) -> Result<Path, Error> {
+        if !policy.allows(self, name) {
             return Err(anyhow!("Illegal: {}", name));
         }
         let mut path = self.clone();
-        let seen_before = !path.seen.insert(name.to_string());
-        if name.chars().all(|c| c.is_ascii_lowercase()) && seen_before {
-            path.has_visited_small_room_twice = true;
+        let visits = path.visits.entry(name.to_string()).or_insert(0);
+        *visits += 1;
+        if is_small(name) && *visits == 2 {
+            path.small_caves_visited_twice += 1;
         }
         path.visited.push(name.to_string());
         Ok(path)
     }
+}
+
+impl Policy {
+    fn part_1() -> Policy {
+        Policy::default()
+    }
+
+    fn part_2() -> Policy {
+        Policy {
+            limits: HashMap::from([("start".to_string(), 1)]),
+            twice_budget: 1,
+            ..Policy::default()
+        }
+    }
+
+    fn allows(&self, path: &Path, name: &str) -> bool {
+        self.allows_visit(name, path.visits(name), path.small_caves_visited_twice)
+    }
+
+    fn allows_visit(&self, name: &str, visits: usize, small_caves_visited_twice: usize) -> bool {
+        if self.forbidden.contains(name) {
+            false
+        } else if let Some(&limit) = self.limits.get(name) {
+            visits < limit
+        } else if is_small(name) {
+            visits == 0 || (visits == 1 && small_caves_visited_twice < self.twice_budget)
+        } else {
+            true
+        }
+    }
 
-    fn is_illegal_part_1_path(&self, name: &str) -> bool {
-        name.chars().all(|c| c.is_ascii_lowercase()) && self.seen.contains(name)
+    /// The number of visits to `name` beyond which this policy treats every
+    /// count the same.
+    fn distinct_visits(&self, name: &str) -> usize {
+        if let Some(&limit) = self.limits.get(name) {
+            limit
+        } else if is_small(name) {
+            if self.twice_budget > 0 {
+                2
+            } else {
+                1
+            }
+        } else if self.required.contains(name) {
+            1
+        } else {
+            0
+        }
     }
 
-    fn is_illegal_part_2_path(&self, name: &str) -> bool {
-        name == "start"
-            || (name.chars().all(|c| c.is_ascii_lowercase())
-                && self.seen.contains(name)
-                && self.has_visited_small_room_twice)
+    fn is_complete(&self, path: &Path) -> bool {
+        self.required.iter().all(|name| path.visits(name) > 0)
     }
 }
 
+fn is_small(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_lowercase())
+}
+
 #[test]
 fn example() {
     assert_eq!(
@@ -122,7 +420,7 @@ A-b
 b-d
 A-end
 b-end",
-            false
+            &Policy::part_1()
         )
         .unwrap()
         .len(),
@@ -137,10 +435,216 @@ A-b
 b-d
 A-end
 b-end",
-            true
+            &Policy::part_2()
         )
         .unwrap()
         .len(),
         36
     );
 }
+
+#[test]
+fn counting() {
+    let inputs = [
+        "start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end",
+        "dc-end
+HN-start
+start-kj
+dc-start
+dc-HN
+LN-dc
+HN-end
+kj-sa
+kj-HN
+kj-dc",
+        "fs-end
+he-DX
+fs-he
+start-DX
+pj-DX
+end-zg
+zg-sl
+zg-pj
+pj-he
+RW-he
+fs-DX
+pj-RW
+zg-RW
+start-pj
+he-WI
+zg-he
+pj-fs
+start-RW",
+    ];
+    let expected = [(10, 36), (19, 103), (226, 3509)];
+    for (input, (part_1, part_2)) in inputs.iter().zip(expected) {
+        assert_eq!(count_paths(input, &Policy::part_1()).unwrap(), part_1);
+        assert_eq!(count_paths(input, &Policy::part_2()).unwrap(), part_2);
+        assert_eq!(
+            paths(input, &Policy::part_2()).unwrap().len() as u64,
+            part_2
+        );
+    }
+    let names: Vec<String> = (0..50u8)
+        .map(|i| format!("{}{}", (b'a' + i / 26) as char, (b'a' + i % 26) as char))
+        .collect();
+    let mut input = format!("start-{}\n{}-end", names[0], names[49]);
+    for pair in names.windows(2) {
+        input.push_str(&format!("\n{}-{}", pair[0], pair[1]));
+    }
+    assert_eq!(count_paths(&input, &Policy::part_1()).unwrap(), 1);
+    assert!(count_paths(&input, &Policy::part_2()).is_err());
+}
+
+#[test]
+fn policies() {
+    let input = "start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end";
+    let policy = Policy {
+        forbidden: HashSet::from(["c".to_string()]),
+        ..Policy::part_1()
+    };
+    assert_eq!(paths(input, &policy).unwrap().len(), 5);
+    let policy = Policy {
+        required: HashSet::from(["c".to_string()]),
+        ..Policy::part_1()
+    };
+    assert!(paths(input, &policy)
+        .unwrap()
+        .iter()
+        .all(|path| path.visited.contains(&"c".to_string())));
+    assert_eq!(paths(input, &policy).unwrap().len(), 5);
+    let policy = Policy {
+        limits: HashMap::from([("start".to_string(), 1), ("A".to_string(), 2)]),
+        twice_budget: 2,
+        ..Policy::default()
+    };
+    let found = paths(input, &policy).unwrap();
+    assert!(found.iter().all(|path| path.visits("A") <= 2));
+    assert!(found.iter().any(|path| path.small_caves_visited_twice == 1));
+    let policy = Policy {
+        twice_budget: 2,
+        ..Policy::part_2()
+    };
+    assert!(paths(input, &policy).unwrap().len() > 36);
+
+    let policies = [
+        Policy {
+            forbidden: HashSet::from(["c".to_string()]),
+            ..Policy::part_2()
+        },
+        Policy {
+            required: HashSet::from(["c".to_string(), "A".to_string()]),
+            ..Policy::part_2()
+        },
+        Policy {
+            limits: HashMap::from([("start".to_string(), 1), ("A".to_string(), 2)]),
+            twice_budget: 2,
+            ..Policy::default()
+        },
+        Policy {
+            limits: HashMap::from([("start".to_string(), 1), ("b".to_string(), 3)]),
+            twice_budget: 1,
+            ..Policy::default()
+        },
+        Policy {
+            required: HashSet::from(["missing".to_string()]),
+            ..Policy::part_1()
+        },
+    ];
+    for policy in &policies {
+        assert_eq!(
+            count_paths(input, policy).unwrap(),
+            paths(input, policy).unwrap().len() as u64
+        );
+    }
+}
+
+#[test]
+fn graphviz() {
+    let input = "start-A
+A-b
+A-end";
+    let caves = caves(input).unwrap();
+    assert_eq!(
+        dot(&caves, None),
+        r#"graph caves {
+    "A" [shape=box, style=filled, fillcolor=lightgray];
+    "b" [shape=ellipse];
+    "end" [shape=ellipse];
+    "start" [shape=ellipse];
+    "A" -- "b";
+    "A" -- "end";
+    "A" -- "start";
+}
+"#
+    );
+    let path = Path::new("start")
+        .with("A", &Policy::part_1())
+        .unwrap()
+        .with("end", &Policy::part_1())
+        .unwrap();
+    assert_eq!(
+        dot(&caves, Some(&path)),
+        r#"graph caves {
+    "A" [shape=box, style=filled, fillcolor=lightgray, color=red];
+    "b" [shape=ellipse];
+    "end" [shape=ellipse, color=red];
+    "start" [shape=ellipse, color=red];
+    "A" -- "b";
+    "A" -- "end" [color=red, penwidth=2];
+    "A" -- "start" [color=red, penwidth=2];
+}
+"#
+    );
+}
+
+#[test]
+fn validation() {
+    let system = caves("start-A\nA-B\nA-end\nc-d").unwrap();
+    assert_eq!(
+        validate(&system, &Policy::part_1()),
+        vec![
+            Problem::AdjacentBigCaves("A".to_string(), "B".to_string()),
+            Problem::Unreachable("c".to_string()),
+            Problem::Unreachable("d".to_string()),
+        ]
+    );
+    assert!(paths("start-A\nA-B\nA-end", &Policy::part_1()).is_err());
+    assert!(count_paths("start-A\nA-B\nA-end", &Policy::part_1()).is_err());
+    let system = caves("start-A\nA-B\nA-B\nB-A\nA-end").unwrap();
+    assert_eq!(
+        validate(&system, &Policy::part_1()),
+        vec![Problem::AdjacentBigCaves("A".to_string(), "B".to_string())]
+    );
+    let policy = Policy {
+        limits: HashMap::from([("A".to_string(), 2)]),
+        ..Policy::part_1()
+    };
+    assert!(validate(&system, &policy).is_empty());
+    assert_eq!(
+        count_paths("start-A\nA-B\nA-end", &policy).unwrap(),
+        paths("start-A\nA-B\nA-end", &policy).unwrap().len() as u64
+    );
+    let system = caves("start-a\na-b").unwrap();
+    assert_eq!(
+        validate(&system, &Policy::part_1()),
+        vec![Problem::MissingCave("end".to_string())]
+    );
+    let system = caves("end-a").unwrap();
+    assert_eq!(
+        validate(&system, &Policy::part_1()),
+        vec![Problem::MissingCave("start".to_string())]
+    );
+}
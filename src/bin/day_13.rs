@@ -1,32 +1,104 @@
 use anyhow::{anyhow, Error};
 use std::{
-    collections::{HashSet, VecDeque},
-    fmt,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
     str::FromStr,
 };
 
 fn main() {
     let input = include_str!("../../inputs/day_13.txt");
+    if std::env::args().any(|arg| arg == "--history") {
+        let format = if std::env::args().any(|arg| arg == "--pbm") {
+            Format::Pbm
+        } else {
+            Format::Text
+        };
+        let mut instructions = Instructions::new(input).unwrap();
+        for (i, stage) in instructions.history().unwrap().iter().enumerate() {
+            match format {
+                Format::Text => print!("{}", stage.render(format)),
+                Format::Pbm => {
+                    let path = format!("day_13_fold_{}.pbm", i + 1);
+                    fs::write(&path, stage.render(format)).unwrap();
+                    println!(
+                        "{}: {} visible dots, wrote {}",
+                        stage.fold, stage.visible_dots, path
+                    );
+                }
+            }
+        }
+        return;
+    }
     let mut instructions = Instructions::new(input).unwrap();
     instructions.fold_one().unwrap();
     println!("Part 1: {}", instructions.visible_dots());
-    instructions.fold();
-    println!("Part 2:\n{}", instructions);
+    instructions.fold().unwrap();
+    println!("Part 2: {}", instructions.letters().unwrap());
+    if std::env::args().any(|arg| arg == "--origins") {
+        let mut dots: Vec<Dot> = instructions.dots.keys().cloned().collect();
+        dots.sort_unstable();
+        for dot in dots {
+            let origins: Vec<String> = instructions
+                .origins(dot)
+                .iter()
+                .map(|origin| format!("{},{}", origin.x, origin.y))
+                .collect();
+            println!("{},{} came from {}", dot.x, dot.y, origins.join(" "));
+        }
+    }
 }
 
+const FONT: [(char, [&str; 6]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
 #[derive(Debug)]
 struct Instructions {
-    dots: HashSet<Dot>,
+    dots: HashMap<Dot, Vec<Dot>>,
     folds: VecDeque<Fold>,
+    width: i64,
+    height: i64,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 struct Dot {
     x: i64,
     y: i64,
 }
 
 #[derive(Debug)]
+struct Stage {
+    fold: Fold,
+    dots: HashSet<Dot>,
+    visible_dots: usize,
+    width: i64,
+    height: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text,
+    Pbm,
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Fold {
     Up(i64),
     Left(i64),
@@ -34,73 +106,165 @@ enum Fold {
 
 impl Instructions {
     fn new(input: &str) -> Result<Instructions, Error> {
-        let mut dots = HashSet::new();
+        let mut dots = HashMap::new();
         let mut folds = VecDeque::new();
         let mut in_header = true;
         for line in input.lines() {
             if line.is_empty() {
                 in_header = false;
             } else if in_header {
-                dots.insert(line.parse::<Dot>()?);
+                let dot = line.parse::<Dot>()?;
+                if dot.x < 0 || dot.y < 0 {
+                    return Err(anyhow!("Negative dot: {}", line));
+                }
+                dots.insert(dot, vec![dot]);
             } else {
                 folds.push_back(line.parse::<Fold>()?);
             }
         }
-        Ok(Instructions { dots, folds })
+        Ok(Instructions {
+            width: dots.keys().map(|dot| dot.x + 1).max().unwrap_or(0),
+            height: dots.keys().map(|dot| dot.y + 1).max().unwrap_or(0),
+            dots,
+            folds,
+        })
+    }
+
+    fn fold(&mut self) -> Result<(), Error> {
+        while let Some(fold) = self.folds.pop_front() {
+            self.execute(fold)?;
+        }
+        Ok(())
     }
 
-    fn fold(&mut self) {
+    fn history(&mut self) -> Result<Vec<Stage>, Error> {
+        let mut stages = Vec::new();
         while let Some(fold) = self.folds.pop_front() {
-            self.execute(fold);
+            self.execute(fold)?;
+            stages.push(Stage {
+                fold,
+                dots: self.dots.keys().cloned().collect(),
+                visible_dots: self.visible_dots(),
+                width: self.width,
+                height: self.height,
+            });
         }
+        Ok(stages)
     }
 
     fn fold_one(&mut self) -> Result<(), Error> {
         if let Some(fold) = self.folds.pop_front() {
-            self.execute(fold);
-            Ok(())
+            self.execute(fold)
         } else {
             Err(anyhow!("No more folds!"))
         }
     }
 
-    fn execute(&mut self, fold: Fold) {
-        let mut new_dots = HashSet::new();
-        for dot in &self.dots {
-            new_dots.insert(match fold {
-                Fold::Left(x) => {
-                    if dot.x > x {
-                        Dot {
-                            x: 2 * x - dot.x,
-                            y: dot.y,
-                        }
-                    } else {
-                        *dot
-                    }
-                }
-                Fold::Up(y) => {
-                    if dot.y > y {
-                        Dot {
-                            x: dot.x,
-                            y: 2 * y - dot.y,
-                        }
-                    } else {
-                        *dot
-                    }
-                }
-            });
+    /// Folds the paper, shifting the result so that the larger half keeps
+    /// its coordinates non-negative when the fold isn't down the middle.
+    fn execute(&mut self, fold: Fold) -> Result<(), Error> {
+        let (line, extent) = match fold {
+            Fold::Left(x) => (x, self.width),
+            Fold::Up(y) => (y, self.height),
+        };
+        if line < 0 || line >= extent {
+            return Err(anyhow!("{} is outside of the paper", fold));
+        }
+        let offset = (extent - 1 - 2 * line).max(0);
+        let mirror = |value: i64| {
+            if value == line {
+                Err(anyhow!("A dot lies on the line of {}", fold))
+            } else if value > line {
+                Ok(2 * line - value + offset)
+            } else {
+                Ok(value + offset)
+            }
+        };
+        let mut new_dots: HashMap<Dot, Vec<Dot>> = HashMap::new();
+        for (dot, origins) in &self.dots {
+            let dot = match fold {
+                Fold::Left(_) => Dot {
+                    x: mirror(dot.x)?,
+                    y: dot.y,
+                },
+                Fold::Up(_) => Dot {
+                    x: dot.x,
+                    y: mirror(dot.y)?,
+                },
+            };
+            new_dots.entry(dot).or_default().extend(origins);
+        }
+        match fold {
+            Fold::Left(_) => self.width = line.max(extent - 1 - line),
+            Fold::Up(_) => self.height = line.max(extent - 1 - line),
         }
         self.dots = new_dots;
+        Ok(())
+    }
+
+    fn origins(&self, dot: Dot) -> Vec<Dot> {
+        let mut origins = self.dots.get(&dot).cloned().unwrap_or_default();
+        origins.sort_unstable();
+        origins
     }
 
     fn visible_dots(&self) -> usize {
         self.dots.len()
     }
 
+    fn letters(&self) -> Result<String, Error> {
+        if let Some(dot) = self
+            .dots
+            .keys()
+            .find(|dot| dot.x < 0 || dot.y < 0 || dot.y >= 6)
+        {
+            return Err(anyhow!(
+                "Dot outside of the letter rows: {},{}",
+                dot.x,
+                dot.y
+            ));
+        }
+        let (max_x, _) = self.max();
+        let mut letters = String::new();
+        let mut unrecognized = Vec::new();
+        for column in (0..=max_x).step_by(5) {
+            // Read the spacer column too, so stray dots in it aren't ignored.
+            let glyph: Vec<String> = (0..6)
+                .map(|y| {
+                    (column..column + 5)
+                        .map(|x| {
+                            if self.dots.contains_key(&Dot { x, y }) {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            match FONT.iter().find(|(_, rows)| {
+                rows.iter()
+                    .map(|row| format!("{}.", row))
+                    .eq(glyph.iter().cloned())
+            }) {
+                Some(&(letter, _)) => letters.push(letter),
+                None => unrecognized.push(column.to_string()),
+            }
+        }
+        if unrecognized.is_empty() {
+            Ok(letters)
+        } else {
+            Err(anyhow!(
+                "Unrecognized glyphs at columns: {}",
+                unrecognized.join(", ")
+            ))
+        }
+    }
+
     fn max(&self) -> (i64, i64) {
         let mut max_x = i64::MIN;
         let mut max_y = i64::MIN;
-        for dot in &self.dots {
+        for dot in self.dots.keys() {
             if dot.x > max_x {
                 max_x = dot.x;
             }
@@ -112,12 +276,48 @@ impl Instructions {
     }
 }
 
+impl Stage {
+    fn render(&self, format: Format) -> String {
+        let mut output = match format {
+            Format::Text => format!("{}: {} visible dots\n", self.fold, self.visible_dots),
+            Format::Pbm => format!("P1\n# {}\n{} {}\n", self.fold, self.width, self.height),
+        };
+        for y in 0..self.height {
+            let row: Vec<&str> = (0..self.width)
+                .map(|x| match (format, self.dots.contains(&Dot { x, y })) {
+                    (Format::Text, true) => "#",
+                    (Format::Text, false) => " ",
+                    (Format::Pbm, true) => "1",
+                    (Format::Pbm, false) => "0",
+                })
+                .collect();
+            match format {
+                Format::Text => output.push_str(&row.concat()),
+                Format::Pbm => output.push_str(&row.join(" ")),
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "text" => Ok(Format::Text),
+            "pbm" => Ok(Format::Pbm),
+            _ => Err(anyhow!("Invalid format: {}", s)),
+        }
+    }
+}
+
 impl fmt::Display for Instructions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let (max_x, max_y) = self.max();
         for y in 0..=max_y {
             for x in 0..=max_x {
-                let c = if self.dots.contains(&Dot { x, y }) {
+                let c = if self.dots.contains_key(&Dot { x, y }) {
                     '#'
                 } else {
                     ' '
@@ -148,6 +348,15 @@ impl FromStr for Dot {
     }
 }
 
+impl fmt::Display for Fold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fold::Up(y) => write!(f, "fold along y={}", y),
+            Fold::Left(x) => write!(f, "fold along x={}", x),
+        }
+    }
+}
+
 impl FromStr for Fold {
     type Err = Error;
     fn from_str(s: &str) -> Result<Fold, Error> {
@@ -193,4 +402,100 @@ fold along x=5";
     let mut instructions = Instructions::new(input).unwrap();
     instructions.fold_one().unwrap();
     assert_eq!(instructions.visible_dots(), 17);
+    instructions.fold().unwrap();
+    assert_eq!(instructions.visible_dots(), 16);
+    assert_eq!(
+        instructions.origins(Dot { x: 0, y: 0 }),
+        vec![Dot { x: 0, y: 14 }]
+    );
+}
+
+#[test]
+fn folding() {
+    let mut instructions = Instructions::new("0,0\n2,0\n4,0\n4,1\n\nfold along x=1").unwrap();
+    instructions.fold().unwrap();
+    assert_eq!(instructions.to_string(), "# #\n#  \n");
+    assert_eq!(
+        instructions.origins(Dot { x: 0, y: 0 }),
+        vec![Dot { x: 4, y: 0 }]
+    );
+    assert_eq!(
+        instructions.origins(Dot { x: 2, y: 0 }),
+        vec![Dot { x: 0, y: 0 }, Dot { x: 2, y: 0 }]
+    );
+    assert!(instructions.origins(Dot { x: 1, y: 0 }).is_empty());
+
+    let mut instructions = Instructions::new("1,0\n2,0\n\nfold along x=1").unwrap();
+    assert_eq!(
+        instructions.fold().unwrap_err().to_string(),
+        "A dot lies on the line of fold along x=1"
+    );
+    let mut instructions = Instructions::new("0,0\n\nfold along y=3").unwrap();
+    assert!(instructions.fold().is_err());
+    assert!(Instructions::new("-1,0").is_err());
+}
+
+#[test]
+fn letters() {
+    let input = "0,0
+1,0
+2,0
+3,0
+0,1
+0,2
+1,2
+2,2
+0,3
+0,4
+0,5
+5,0
+5,1
+5,2
+5,3
+5,4
+5,5
+6,5
+7,5
+8,5
+";
+    let instructions = Instructions::new(input).unwrap();
+    assert_eq!(instructions.letters().unwrap(), "FL");
+    let instructions = Instructions::new("0,0\n5,0\n5,1\n6,5").unwrap();
+    assert_eq!(
+        instructions.letters().unwrap_err().to_string(),
+        "Unrecognized glyphs at columns: 0, 5"
+    );
+    let instructions = Instructions::new("0,6").unwrap();
+    assert!(instructions.letters().is_err());
+    let instructions = Instructions::new(&format!("{}4,2\n", input)).unwrap();
+    assert_eq!(
+        instructions.letters().unwrap_err().to_string(),
+        "Unrecognized glyphs at columns: 0"
+    );
+}
+
+#[test]
+fn history() {
+    let input = "0,0
+4,0
+0,2
+4,2
+
+fold along x=2
+fold along y=1";
+    let mut instructions = Instructions::new(input).unwrap();
+    let stages = instructions.history().unwrap();
+    assert_eq!(stages.len(), 2);
+    assert_eq!(stages[0].visible_dots, 2);
+    assert_eq!(stages[1].visible_dots, 1);
+    assert_eq!(
+        stages[0].render(Format::Text),
+        "fold along x=2: 2 visible dots\n# \n  \n# \n"
+    );
+    assert_eq!(
+        stages[1].render(Format::Pbm),
+        "P1\n# fold along y=1\n2 1\n1 0\n"
+    );
+    assert!(instructions.folds.is_empty());
+    assert!("png".parse::<Format>().is_err());
 }
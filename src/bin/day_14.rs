@@ -1,11 +1,28 @@
 use anyhow::{anyhow, Error};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+const MAX_EXPANSION: usize = 1 << 20;
 
 fn main() {
     let input = include_str!("../../inputs/day_14.txt");
-    let mut polymer = Polymer::new(input).unwrap();
-    println!("Part 1: {}", polymer.run(10));
-    println!("Part 2: {}", polymer.run(40));
+    let polymer = Polymer::new(input).unwrap();
+    if std::env::args().any(|arg| arg == "--histogram") {
+        for (element, count) in polymer.run(40) {
+            println!("{}: {}", element, count);
+        }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--expand") {
+        let expanded = polymer.expand(10).unwrap();
+        if histogram(expanded.chars()) == polymer.run(10) {
+            println!("Pair counts match the expanded polymer");
+        } else {
+            println!("Pair counts DO NOT match the expanded polymer");
+        }
+        return;
+    }
+    println!("Part 1: {}", difference(&polymer.run(10)).unwrap());
+    println!("Part 2: {}", difference(&polymer.run(40)).unwrap());
 }
 
 #[derive(Debug)]
@@ -43,12 +60,10 @@ impl Polymer {
         Ok(Polymer { template, rules })
     }
 
-    fn run(&mut self, times: usize) -> usize {
-        let mut chars = HashMap::new();
-        for &c in &self.template {
-            let entry = chars.entry(c).or_insert(0);
-            *entry += 1;
-        }
+    /// Counts the elements after `times` insertion steps by tracking pair
+    /// counts, so the polymer itself is never built.
+    fn run(&self, times: usize) -> BTreeMap<char, usize> {
+        let mut chars = histogram(self.template.iter().cloned());
         let mut counts = HashMap::new();
         for (&a, &b) in self.template.iter().zip(self.template.iter().skip(1)) {
             let entry = counts.entry((a, b)).or_insert(0);
@@ -56,19 +71,66 @@ impl Polymer {
         }
         for _ in 0..times {
             let mut new_counts = HashMap::new();
-            for (&(a, b), count) in counts.iter() {
-                let c = self.rules[&(a, b)];
-                let entry = new_counts.entry((a, c)).or_insert(0);
-                *entry += count;
-                let entry = new_counts.entry((c, b)).or_insert(0);
-                *entry += count;
-                let entry = chars.entry(c).or_insert(0);
-                *entry += count;
+            for (&(a, b), &count) in counts.iter() {
+                if let Some(&c) = self.rules.get(&(a, b)) {
+                    let entry = new_counts.entry((a, c)).or_insert(0);
+                    *entry += count;
+                    let entry = new_counts.entry((c, b)).or_insert(0);
+                    *entry += count;
+                    let entry = chars.entry(c).or_insert(0);
+                    *entry += count;
+                } else {
+                    let entry = new_counts.entry((a, b)).or_insert(0);
+                    *entry += count;
+                }
             }
             std::mem::swap(&mut counts, &mut new_counts);
         }
-        chars.values().max().unwrap() - chars.values().min().unwrap()
+        chars
     }
+
+    /// Builds the actual polymer after `times` insertion steps.
+    fn expand(&self, times: usize) -> Result<String, Error> {
+        let mut polymer = self.template.clone();
+        for step in 0..times {
+            if polymer.len() * 2 > MAX_EXPANSION {
+                return Err(anyhow!(
+                    "Polymer is too long to expand past step {}: {} elements",
+                    step,
+                    polymer.len()
+                ));
+            }
+            let mut next = Vec::with_capacity(polymer.len() * 2);
+            for (i, &a) in polymer.iter().enumerate() {
+                next.push(a);
+                if let Some(&b) = polymer.get(i + 1) {
+                    if let Some(&c) = self.rules.get(&(a, b)) {
+                        next.push(c);
+                    }
+                }
+            }
+            polymer = next;
+        }
+        Ok(polymer.into_iter().collect())
+    }
+}
+
+fn histogram(elements: impl Iterator<Item = char>) -> BTreeMap<char, usize> {
+    let mut histogram = BTreeMap::new();
+    for c in elements {
+        let entry = histogram.entry(c).or_insert(0);
+        *entry += 1;
+    }
+    histogram
+}
+
+fn difference(histogram: &BTreeMap<char, usize>) -> Result<usize, Error> {
+    let max = histogram
+        .values()
+        .max()
+        .ok_or_else(|| anyhow!("Empty polymer"))?;
+    let min = histogram.values().min().expect("histogram is not empty");
+    Ok(max - min)
 }
 
 #[test]
@@ -91,7 +153,51 @@ BB -> N
 BC -> B
 CC -> N
 CN -> C";
-    let mut polymer = Polymer::new(input).unwrap();
-    assert_eq!(polymer.run(10), 1588);
-    assert_eq!(polymer.run(40), 2188189693529);
+    let polymer = Polymer::new(input).unwrap();
+    assert_eq!(difference(&polymer.run(10)).unwrap(), 1588);
+    assert_eq!(difference(&polymer.run(40)).unwrap(), 2188189693529);
+    let histogram = polymer.run(10);
+    assert_eq!(histogram[&'B'], 1749);
+    assert_eq!(histogram[&'C'], 298);
+    assert_eq!(histogram[&'H'], 161);
+    assert_eq!(histogram[&'N'], 865);
+}
+
+#[test]
+fn expansion() {
+    let input = "NNCB
+
+CH -> B
+HH -> N
+CB -> H
+NH -> C
+HB -> C
+HC -> B
+HN -> C
+NN -> C
+BH -> H
+NC -> B
+NB -> B
+BN -> B
+BB -> N
+BC -> B
+CC -> N
+CN -> C";
+    let polymer = Polymer::new(input).unwrap();
+    assert_eq!(polymer.expand(2).unwrap(), "NBCCNBBBCBHCB");
+    for times in 0..=10 {
+        assert_eq!(
+            histogram(polymer.expand(times).unwrap().chars()),
+            polymer.run(times)
+        );
+    }
+    assert!(polymer.expand(40).is_err());
+
+    let polymer = Polymer::new("ABA\n\nAB -> C").unwrap();
+    assert_eq!(polymer.expand(2).unwrap(), "ACBA");
+    assert_eq!(
+        histogram(polymer.expand(2).unwrap().chars()),
+        polymer.run(2)
+    );
+    assert!(difference(&Polymer::new("").unwrap().run(1)).is_err());
 }